@@ -1,16 +1,27 @@
-#![feature(proc_macro_span)]
+#![cfg_attr(not(feature = "stable"), feature(proc_macro_span))]
 
 extern crate proc_macro;
 
 use std::{
+    cell::RefCell,
+    collections::HashMap,
     fs,
     path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
 };
 
+#[cfg(not(feature = "stable"))]
 use proc_macro::Span;
 use proc_macro2::LineColumn;
-use syn::{spanned::Spanned, Item};
+use syn::{ext::IdentExt, spanned::Spanned, Item};
 
+#[cfg(feature = "stable")]
+mod stable;
+#[cfg(feature = "stable")]
+pub use stable::{find_call_sites, CallSite};
+
+#[cfg(not(feature = "stable"))]
 pub fn get_source_info() -> Option<(String, usize)> {
     let span = Span::call_site().source();
     let file_path = span.local_file()?;
@@ -19,6 +30,19 @@ pub fn get_source_info() -> Option<(String, usize)> {
     Some((file_path, line_number))
 }
 
+/// Without `proc_macro_span`, the call site comes from the span locations that Rust 1.88
+/// stabilized; older compilers report none, so this returns `None` there.
+#[cfg(feature = "stable")]
+pub fn get_source_info() -> Option<(String, usize)> {
+    let span = proc_macro2::Span::call_site();
+    let file_path = span.local_file()?;
+    let line_number = span.start().line;
+    if line_number == 0 {
+        return None;
+    }
+    Some((file_path.to_string_lossy().to_string(), line_number))
+}
+
 pub fn find_module_path(file_path: &str, line_number: usize) -> Option<String> {
     let module_root = module_root_from_file(file_path);
     find_module_path_in_file(file_path, line_number, &module_root)
@@ -57,6 +81,10 @@ pub fn module_root_from_file(file_path: &str) -> PathBuf {
         root.push("src");
         return root;
     }
+    // Cargo passes paths relative to the package directory.
+    if normalized.starts_with("src/") {
+        return PathBuf::from("src");
+    }
 
     Path::new(file_path)
         .parent()
@@ -65,6 +93,13 @@ pub fn module_root_from_file(file_path: &str) -> PathBuf {
 }
 
 pub fn module_path_from_file_with_root(file_path: &str, module_root: &Path) -> String {
+    let roots = crate_root_files(None, module_root);
+    if let Some(module) = module_files_reaching(&roots, Path::new(file_path))
+        .and_then(|files| module_of_file(&files, Path::new(file_path)))
+    {
+        return module;
+    }
+
     let normalized = file_path.replace('\\', "/");
     let module_root_norm = module_root.to_string_lossy().replace('\\', "/");
 
@@ -98,10 +133,8 @@ pub fn find_module_path_in_file(
     module_root: &Path,
 ) -> Option<String> {
     let content = fs::read_to_string(file_path).ok()?;
-    let parsed = syn::parse_file(&content).ok()?;
 
     let base = module_path_from_file_with_root(file_path, module_root);
-    let mut best_stack: Vec<String> = Vec::new();
 
     fn span_contains_line(span: proc_macro2::Span, line: usize) -> bool {
         let LineColumn { line: start, .. } = span.start();
@@ -128,7 +161,11 @@ pub fn find_module_path_in_file(
         }
     }
 
-    visit_items(&parsed.items, line_number, &mut Vec::new(), &mut best_stack);
+    let best_stack = with_source_locations(&content, |parsed| {
+        let mut best_stack: Vec<String> = Vec::new();
+        visit_items(&parsed.items, line_number, &mut Vec::new(), &mut best_stack);
+        best_stack
+    })?;
 
     if best_stack.is_empty() {
         return Some(base);
@@ -142,12 +179,44 @@ pub fn find_module_path_in_file(
     }
 }
 
+/// Parses source text with proc-macro2's fallback implementation so spans carry real line
+/// numbers, and runs `f` on the result. Inside a proc macro, the compiler's implementation gives
+/// every token parsed from a string the call-site span, which makes line lookups meaningless.
+///
+/// The fallback stays forced while `f` runs, so `f` must not produce tokens for the macro output.
+pub fn with_source_locations<R>(content: &str, f: impl FnOnce(&syn::File) -> R) -> Option<R> {
+    proc_macro2::fallback::force();
+    let result = syn::parse_file(content).ok().map(|parsed| f(&parsed));
+    proc_macro2::fallback::unforce();
+    result
+}
+
+/// The module path of `file_path` in the crate that `current_file` belongs to, honoring `mod`
+/// declarations with `#[path]`. Falls back to `module_path_from_file_with_root`.
+pub fn module_path_in_crate(file_path: &str, current_file: &str, module_root: &Path) -> String {
+    let files = crate_module_files(current_file, module_root);
+    module_of_file(&files, Path::new(file_path))
+        .unwrap_or_else(|| module_path_from_file_with_root(file_path, module_root))
+}
+
+/// The file declaring `module_path`. Modules are looked up among those reachable from the crate
+/// root through `mod` declarations, honoring `#[path]`, then at the conventional `{rel}.rs` and
+/// `{rel}/mod.rs`. An inline module resolves to the file of its closest ancestor with a file.
 pub fn module_path_to_file(
     module_path: &str,
     current_file: &str,
     module_root: &Path,
 ) -> Option<PathBuf> {
     let module_path = module_path.strip_prefix("crate::").unwrap_or(module_path);
+    let files = if module_path == "crate" || module_path.is_empty() {
+        Vec::new()
+    } else {
+        crate_module_files(current_file, module_root)
+    };
+    if let Some((_, file)) = files.iter().find(|(module, _)| module == module_path) {
+        return Some(file.clone());
+    }
+
     if module_path == "crate" || module_path.is_empty() {
         let lib = module_root.join("lib.rs");
         if lib.exists() {
@@ -173,9 +242,225 @@ pub fn module_path_to_file(
     if candidate.exists() {
         return Some(candidate);
     }
+    let mut ancestor = module_path;
+    while let Some((parent, _)) = ancestor.rsplit_once("::") {
+        if let Some((_, file)) = files.iter().find(|(module, _)| module == parent) {
+            return Some(file.clone());
+        }
+        ancestor = parent;
+    }
     None
 }
 
+/// An out-of-line `mod name;` declaration.
+#[derive(Clone, Debug)]
+pub struct ModDecl {
+    /// The inline modules the declaration sits in, outermost first, with their `#[path]`.
+    pub inline: Vec<(String, Option<String>)>,
+    pub name: String,
+    /// The declaration's own `#[path]`.
+    pub path: Option<String>,
+}
+
+impl ModDecl {
+    /// The declarations among `items`, including those in inline modules. Modules declared in
+    /// function bodies are not included.
+    pub fn collect(items: &[Item]) -> Vec<ModDecl> {
+        fn visit(
+            items: &[Item],
+            inline: &mut Vec<(String, Option<String>)>,
+            out: &mut Vec<ModDecl>,
+        ) {
+            for item in items {
+                let Item::Mod(module) = item else { continue };
+                let name = module.ident.unraw().to_string();
+                let path = path_attr(&module.attrs);
+                match &module.content {
+                    Some((_, items)) => {
+                        inline.push((name, path));
+                        visit(items, inline, out);
+                        inline.pop();
+                    }
+                    None => out.push(ModDecl {
+                        inline: inline.clone(),
+                        name,
+                        path,
+                    }),
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        visit(items, &mut Vec::new(), &mut out);
+        out
+    }
+
+    /// The file this declaration in `file` loads, with the directory holding the files of that
+    /// module's children, following rustc's rules. `dir` holds the files of `file`'s children.
+    ///
+    /// `#[path]` is relative to the directory of `file`, or to `dir` plus the inline modules
+    /// when the declaration sits in any. A file loaded through `#[path]` keeps its children next
+    /// to it, like a `mod.rs`.
+    pub fn resolve(&self, file: &Path, dir: &Path) -> Option<(PathBuf, PathBuf)> {
+        let mut inline_dir = dir.to_path_buf();
+        for (name, path) in &self.inline {
+            inline_dir.push(path.as_deref().unwrap_or(name));
+        }
+
+        if let Some(path) = &self.path {
+            let base = if self.inline.is_empty() {
+                file.parent().map(Path::to_path_buf).unwrap_or_default()
+            } else {
+                inline_dir
+            };
+            let child = base.join(path);
+            let child_dir = child.parent().map(Path::to_path_buf).unwrap_or_default();
+            return child.is_file().then_some((child, child_dir));
+        }
+
+        let child_dir = inline_dir.join(&self.name);
+        [
+            inline_dir.join(format!("{}.rs", self.name)),
+            child_dir.join("mod.rs"),
+        ]
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+        .map(|child| (child, child_dir))
+    }
+}
+
+/// The string value of a `#[path = "..."]` attribute among `attrs`.
+fn path_attr(attrs: &[syn::Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| {
+        let syn::Meta::NameValue(meta) = &attr.meta else {
+            return None;
+        };
+        if !meta.path.is_ident("path") {
+            return None;
+        }
+        match &meta.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(lit),
+                ..
+            }) => Some(lit.value()),
+            _ => None,
+        }
+    })
+}
+
+/// Every module of the crate that `current_file` belongs to which has a file of its own, with
+/// its module path (`crate` for the root), found by following `mod` declarations from the crate
+/// root.
+pub fn crate_module_files(current_file: &str, module_root: &Path) -> Vec<(String, PathBuf)> {
+    let roots = crate_root_files(Some(current_file), module_root);
+    module_files_reaching(&roots, Path::new(current_file))
+        .or_else(|| roots.first().map(|root| module_files(root)))
+        .unwrap_or_default()
+}
+
+/// Candidate crate roots under `module_root`: `lib.rs` and `main.rs`, and outside a `src`
+/// directory the current file itself.
+fn crate_root_files(current_file: Option<&str>, module_root: &Path) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = ["lib.rs", "main.rs"]
+        .iter()
+        .map(|name| module_root.join(name))
+        .filter(|root| root.is_file())
+        .collect();
+    let in_src = module_root.ends_with("src");
+    if let Some(current) = current_file.filter(|_| !in_src) {
+        roots.push(PathBuf::from(current));
+    }
+    roots
+}
+
+/// The modules of the first of `roots` whose tree includes `file`.
+fn module_files_reaching(roots: &[PathBuf], file: &Path) -> Option<Vec<(String, PathBuf)>> {
+    roots
+        .iter()
+        .map(|root| module_files(root))
+        .find(|files| module_of_file(files, file).is_some())
+}
+
+fn module_of_file(files: &[(String, PathBuf)], file: &Path) -> Option<String> {
+    files
+        .iter()
+        .find(|(_, seen)| seen == file)
+        .map(|(module, _)| module.clone())
+}
+
+/// The modules below the crate root `root` that have files of their own.
+fn module_files(root: &Path) -> Vec<(String, PathBuf)> {
+    fn walk(file: &Path, dir: &Path, module: &str, files: &mut Vec<(String, PathBuf)>) {
+        let Some(decls) = mod_decls(file) else {
+            return;
+        };
+        for decl in decls.iter() {
+            let Some((child, child_dir)) = decl.resolve(file, dir) else {
+                continue;
+            };
+            if files.iter().any(|(_, seen)| *seen == child) {
+                continue;
+            }
+            let mut segments: Vec<&str> = if module == "crate" {
+                Vec::new()
+            } else {
+                vec![module]
+            };
+            segments.extend(decl.inline.iter().map(|(name, _)| name.as_str()));
+            segments.push(&decl.name);
+            let child_module = segments.join("::");
+            files.push((child_module.clone(), child.clone()));
+            walk(&child, &child_dir, &child_module, files);
+        }
+    }
+
+    let mut files = vec![("crate".to_string(), root.to_path_buf())];
+    let dir = root.parent().map(Path::to_path_buf).unwrap_or_default();
+    walk(root, &dir, "crate", &mut files);
+    files
+}
+
+/// Module declarations with the modification time and length of the file they were read from.
+struct CachedDecls {
+    modified: Option<SystemTime>,
+    len: u64,
+    decls: Rc<Vec<ModDecl>>,
+}
+
+thread_local! {
+    /// Module declarations by file, checked against the file before each use.
+    static DECLS: RefCell<HashMap<PathBuf, CachedDecls>> = RefCell::new(HashMap::new());
+}
+
+fn mod_decls(file: &Path) -> Option<Rc<Vec<ModDecl>>> {
+    let metadata = fs::metadata(file).ok()?;
+    let modified = metadata.modified().ok();
+    let len = metadata.len();
+    let cached = DECLS.with(|decls| {
+        decls
+            .borrow()
+            .get(file)
+            .filter(|cached| cached.modified == modified && cached.len == len)
+            .map(|cached| cached.decls.clone())
+    });
+    if cached.is_some() {
+        return cached;
+    }
+    let content = fs::read_to_string(file).ok()?;
+    let decls = Rc::new(ModDecl::collect(&syn::parse_file(&content).ok()?.items));
+    DECLS.with(|cache| {
+        cache.borrow_mut().insert(
+            file.to_path_buf(),
+            CachedDecls {
+                modified,
+                len,
+                decls: decls.clone(),
+            },
+        )
+    });
+    Some(decls)
+}
+
 pub fn get_pseudo_module_path() -> String {
     get_source_info()
         .and_then(|(file, line)| find_module_path(&file, line))
@@ -0,0 +1,480 @@
+//! Locating macro calls without `proc_macro_span`.
+//!
+//! Stable proc macros cannot ask the compiler which file they were called from, so the files of
+//! the crate being compiled are found from the environment Cargo sets for rustc
+//! (`CARGO_MANIFEST_DIR`, `CARGO_CRATE_NAME` and `CARGO_BIN_NAME`), following `mod` declarations
+//! from the crate root, and the call is looked up in them by its contents.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{spanned::Spanned, visit::Visit, Item};
+
+use crate::ModDecl;
+
+/// A macro call to look up in the crate's source files.
+pub enum CallSite<'a> {
+    /// An attribute macro named `attr` on `item`, e.g. `#[nestum] enum Event { .. }`. The item
+    /// is matched by kind and name (and variant names for enums), so names should be unique
+    /// within the crate.
+    Attribute { attr: &'a str, item: &'a Item },
+    /// A function-like macro called by one of `names` with `tokens`, e.g. `nested! { .. }`. The
+    /// call is matched by its tokens, ignoring whitespace.
+    Macro {
+        names: &'a [&'a str],
+        tokens: &'a TokenStream,
+    },
+}
+
+/// Every place in the crate's source files matching `site`, as file paths and 1-based lines:
+/// the line of the attribute for `CallSite::Attribute`, or of the macro name for
+/// `CallSite::Macro`.
+///
+/// Only files reachable through `mod` declarations from the crate root are searched, so calls
+/// produced by other macros or `include!` are not found.
+pub fn find_call_sites(site: &CallSite<'_>) -> Vec<(String, usize)> {
+    let key = match site {
+        CallSite::Attribute { item, .. } => item_key(item),
+        CallSite::Macro { tokens, .. } => Some(normalize(tokens)),
+    };
+    let Some(key) = key else {
+        return Vec::new();
+    };
+
+    let mut found = Vec::new();
+    let mut visited = Vec::new();
+    for root in crate_roots() {
+        let dir = root.parent().map(Path::to_path_buf).unwrap_or_default();
+        walk_file(&root, &dir, &mut visited, &mut |file, index| {
+            let lines = match site {
+                CallSite::Attribute { attr, .. } => index
+                    .attributes
+                    .iter()
+                    .filter(|entry| entry.key == key && entry.attr == *attr)
+                    .map(|entry| entry.line)
+                    .collect::<Vec<_>>(),
+                CallSite::Macro { names, .. } => index
+                    .macros
+                    .iter()
+                    .filter(|entry| entry.tokens == key && names.contains(&entry.name.as_str()))
+                    .map(|entry| entry.line)
+                    .collect(),
+            };
+            let file = file.to_string_lossy().to_string();
+            found.extend(lines.into_iter().map(|line| (file.clone(), line)));
+        });
+    }
+    found
+}
+
+/// What `find_call_sites` matches items on: their kind and name, plus the variants and field
+/// types of enums, the signature of functions and the trait of impl blocks. Attributes are left
+/// out, since other attribute macros may already have removed theirs.
+fn item_key(item: &Item) -> Option<String> {
+    match item {
+        Item::Enum(item) => {
+            let variants = item
+                .variants
+                .iter()
+                .map(|variant| {
+                    let fields = variant
+                        .fields
+                        .iter()
+                        .map(|field| quote_tokens(&field.ty))
+                        .collect::<Vec<_>>();
+                    format!("{}({})", variant.ident, fields.join(","))
+                })
+                .collect::<Vec<_>>();
+            Some(format!("enum {} {{{}}}", item.ident, variants.join(",")))
+        }
+        Item::Fn(item) => Some(fn_key(&item.sig)),
+        Item::Mod(item) => Some(format!("mod {}", item.ident)),
+        Item::Impl(item) => {
+            let trait_ = item.trait_.as_ref().map(|(_, path, _)| {
+                let path = quote_tokens(path);
+                format!("{path} for ")
+            });
+            Some(format!(
+                "impl {}{}",
+                trait_.unwrap_or_default(),
+                quote_tokens(&item.self_ty)
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn fn_key(sig: &syn::Signature) -> String {
+    format!("fn {}", quote_tokens(sig))
+}
+
+fn quote_tokens(node: &impl ToTokens) -> String {
+    normalize(&node.to_token_stream())
+}
+
+/// Tokens as a string without whitespace, which differs between the compiler's tokens and
+/// tokens parsed from source.
+fn normalize(tokens: &TokenStream) -> String {
+    tokens
+        .to_string()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+/// The root source files of the crate being compiled. Only the target tables of the manifest
+/// are read, and only their `name` and `path` keys.
+fn crate_roots() -> Vec<PathBuf> {
+    let Some(dir) = env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from) else {
+        return Vec::new();
+    };
+    let crate_name = env::var("CARGO_CRATE_NAME").unwrap_or_default();
+    let manifest = fs::read_to_string(dir.join("Cargo.toml")).unwrap_or_default();
+    let targets = manifest_targets(&manifest);
+    let explicit = |tables: &[&str], name: &str| {
+        targets
+            .iter()
+            .filter(|target| tables.contains(&target.table.as_str()))
+            .filter(|target| {
+                target.name.as_deref().map(|n| n.replace('-', "_")) == Some(name.replace('-', "_"))
+            })
+            .filter_map(|target| target.path.as_ref().map(|path| dir.join(path)))
+            .collect::<Vec<_>>()
+    };
+
+    let mut roots = Vec::new();
+    if let Ok(bin) = env::var("CARGO_BIN_NAME") {
+        roots.extend(explicit(&["bin"], &bin));
+        if env::var("CARGO_PKG_NAME").is_ok_and(|package| package == bin) {
+            roots.push(dir.join("src/main.rs"));
+        }
+        roots.push(dir.join("src/bin").join(format!("{bin}.rs")));
+        roots.push(dir.join("src/bin").join(&bin).join("main.rs"));
+    } else {
+        let lib = targets.iter().find(|target| target.table == "lib");
+        let lib_name = lib
+            .and_then(|lib| lib.name.clone())
+            .or_else(|| env::var("CARGO_PKG_NAME").ok())
+            .map(|name| name.replace('-', "_"));
+        if lib_name.as_deref() == Some(crate_name.as_str()) {
+            match lib.and_then(|lib| lib.path.as_ref()) {
+                Some(path) => roots.push(dir.join(path)),
+                None => roots.push(dir.join("src/lib.rs")),
+            }
+        } else {
+            roots.extend(explicit(&["test", "example", "bench"], &crate_name));
+            for kind in ["tests", "examples", "benches"] {
+                roots.push(dir.join(kind).join(format!("{crate_name}.rs")));
+                roots.push(dir.join(kind).join(&crate_name).join("main.rs"));
+            }
+        }
+    }
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for root in roots {
+        if root.is_file() && !unique.contains(&root) {
+            unique.push(root);
+        }
+    }
+    unique
+}
+
+/// A `[lib]`, `[[bin]]`, `[[test]]`, `[[example]]` or `[[bench]]` table of a manifest.
+struct ManifestTarget {
+    table: String,
+    name: Option<String>,
+    path: Option<String>,
+}
+
+fn manifest_targets(manifest: &str) -> Vec<ManifestTarget> {
+    let Ok(manifest) = manifest.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    let mut targets = Vec::new();
+    for table in ["lib", "bin", "test", "example", "bench"] {
+        let entries = match manifest.get(table) {
+            Some(toml::Value::Table(entry)) => vec![entry],
+            Some(toml::Value::Array(entries)) => {
+                entries.iter().filter_map(toml::Value::as_table).collect()
+            }
+            _ => continue,
+        };
+        for entry in entries {
+            let string = |key| {
+                entry
+                    .get(key)
+                    .and_then(toml::Value::as_str)
+                    .map(String::from)
+            };
+            targets.push(ManifestTarget {
+                table: table.to_string(),
+                name: string("name"),
+                path: string("path"),
+            });
+        }
+    }
+    targets
+}
+
+/// The macro calls found in one source file.
+#[derive(Default)]
+struct FileIndex {
+    attributes: Vec<AttributeEntry>,
+    macros: Vec<MacroEntry>,
+    /// Out-of-line `mod name;` declarations.
+    mods: Vec<ModDecl>,
+}
+
+struct AttributeEntry {
+    key: String,
+    /// Last segment of the attribute's path.
+    attr: String,
+    line: usize,
+}
+
+struct MacroEntry {
+    name: String,
+    tokens: String,
+    line: usize,
+}
+
+/// A file index with the modification time and length of the file it was built from.
+struct CachedIndex {
+    modified: Option<SystemTime>,
+    len: u64,
+    index: Rc<FileIndex>,
+}
+
+thread_local! {
+    /// File indexes by path. Proc macros run once per call, and long-lived hosts such as
+    /// language servers keep them loaded across edits, so entries are checked against the file
+    /// before each use.
+    static INDEXES: RefCell<HashMap<PathBuf, CachedIndex>> = RefCell::new(HashMap::new());
+}
+
+fn file_index(file: &Path) -> Option<Rc<FileIndex>> {
+    let metadata = fs::metadata(file).ok()?;
+    let modified = metadata.modified().ok();
+    let len = metadata.len();
+    let cached = INDEXES.with(|indexes| {
+        indexes
+            .borrow()
+            .get(file)
+            .filter(|cached| cached.modified == modified && cached.len == len)
+            .map(|cached| cached.index.clone())
+    });
+    if cached.is_some() {
+        return cached;
+    }
+    let content = fs::read_to_string(file).ok()?;
+    let index = Rc::new(crate::with_source_locations(&content, build_index)?);
+    INDEXES.with(|indexes| {
+        indexes.borrow_mut().insert(
+            file.to_path_buf(),
+            CachedIndex {
+                modified,
+                len,
+                index: index.clone(),
+            },
+        )
+    });
+    Some(index)
+}
+
+fn build_index(file: &syn::File) -> FileIndex {
+    struct Indexer {
+        index: FileIndex,
+    }
+
+    impl Indexer {
+        fn attributes(&mut self, key: Option<String>, attrs: &[syn::Attribute]) {
+            let Some(key) = key else { return };
+            for attr in attrs {
+                if let Some(segment) = attr.path().segments.last() {
+                    self.index.attributes.push(AttributeEntry {
+                        key: key.clone(),
+                        attr: segment.ident.to_string(),
+                        line: attr.span().start().line,
+                    });
+                }
+            }
+        }
+    }
+
+    impl<'ast> Visit<'ast> for Indexer {
+        fn visit_item(&mut self, item: &'ast Item) {
+            let attrs = match item {
+                Item::Enum(item) => &item.attrs,
+                Item::Fn(item) => &item.attrs,
+                Item::Mod(item) => &item.attrs,
+                Item::Impl(item) => &item.attrs,
+                _ => return syn::visit::visit_item(self, item),
+            };
+            self.attributes(item_key(item), attrs);
+            syn::visit::visit_item(self, item);
+        }
+
+        fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+            self.attributes(Some(fn_key(&item.sig)), &item.attrs);
+            syn::visit::visit_impl_item_fn(self, item);
+        }
+    }
+
+    let mut indexer = Indexer {
+        index: FileIndex::default(),
+    };
+    indexer.visit_file(file);
+    let mut index = indexer.index;
+    index.mods = ModDecl::collect(&file.items);
+    // Macro calls are found in the raw tokens, so calls inside other macros count too.
+    scan_macros(file.to_token_stream(), &mut index.macros);
+    index
+}
+
+fn scan_macros(tokens: TokenStream, macros: &mut Vec<MacroEntry>) {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        let TokenTree::Group(group) = token else {
+            continue;
+        };
+        if let [.., TokenTree::Ident(name), TokenTree::Punct(bang)] = &tokens[..i] {
+            if bang.as_char() == '!' {
+                macros.push(MacroEntry {
+                    name: name.to_string(),
+                    tokens: normalize(&group.stream()),
+                    line: name.span().start().line,
+                });
+            }
+        }
+        scan_macros(group.stream(), macros);
+    }
+}
+
+/// Calls `visit` on `file` and every file it declares as a module, with `dir` the directory
+/// holding the files of its child modules.
+fn walk_file(
+    file: &Path,
+    dir: &Path,
+    visited: &mut Vec<PathBuf>,
+    visit: &mut dyn FnMut(&Path, &FileIndex),
+) {
+    if visited.iter().any(|seen| seen == file) {
+        return;
+    }
+    visited.push(file.to_path_buf());
+    let Some(index) = file_index(file) else {
+        return;
+    };
+    visit(file, &index);
+    for decl in index.mods.iter() {
+        if let Some((child, child_dir)) = decl.resolve(file, dir) {
+            walk_file(&child, &child_dir, visited, visit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::manifest_targets;
+
+    fn targets(manifest: &str) -> Vec<(String, Option<String>, Option<String>)> {
+        manifest_targets(manifest)
+            .into_iter()
+            .map(|target| (target.table, target.name, target.path))
+            .collect()
+    }
+
+    fn target(
+        table: &str,
+        name: Option<&str>,
+        path: Option<&str>,
+    ) -> (String, Option<String>, Option<String>) {
+        (
+            table.to_string(),
+            name.map(String::from),
+            path.map(String::from),
+        )
+    }
+
+    #[test]
+    fn trailing_comments() {
+        let manifest = r#"
+            [package]
+            name = "app"
+
+            [lib] # the library
+            name = "app_core" # renamed
+            path = "src/core.rs" # not src/lib.rs
+        "#;
+        assert_eq!(
+            targets(manifest),
+            [target("lib", Some("app_core"), Some("src/core.rs"))]
+        );
+    }
+
+    #[test]
+    fn inline_tables() {
+        let manifest = r#"
+            lib = { path = "src/core.rs" }
+            bin = [{ name = "tool", path = "src/tool.rs" }, { name = "other" }]
+        "#;
+        assert_eq!(
+            targets(manifest),
+            [
+                target("lib", None, Some("src/core.rs")),
+                target("bin", Some("tool"), Some("src/tool.rs")),
+                target("bin", Some("other"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_line_values() {
+        let manifest = r#"
+            [[test]]
+            name = "ui"
+            path = "tests/ui.rs"
+            required-features = [
+                "stable",
+            ]
+            doc = """
+            path = "tests/other.rs"
+            name = "other"
+            """
+        "#;
+        assert_eq!(
+            targets(manifest),
+            [target("test", Some("ui"), Some("tests/ui.rs"))]
+        );
+    }
+
+    #[test]
+    fn keys_and_strings() {
+        let manifest = r#"
+            [[example]]
+            "name" = "demo"
+            path = "examples/a = b.rs"
+
+            [[bench]]
+            name = 'speed'
+            path = "benches/\u0073peed.rs"
+
+            [package.metadata.lib]
+            path = "src/unused.rs"
+        "#;
+        assert_eq!(
+            targets(manifest),
+            [
+                target("example", Some("demo"), Some("examples/a = b.rs")),
+                target("bench", Some("speed"), Some("benches/speed.rs")),
+            ]
+        );
+    }
+}
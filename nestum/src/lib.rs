@@ -1,45 +1,67 @@
-#![feature(proc_macro_span)]
-
 use std::collections::{HashMap, HashSet};
 
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
+use syn::parse::Parser;
+use syn::visit_mut::VisitMut;
 use syn::{
-    Attribute, ExprMatch, Fields, Item, ItemEnum, Meta, MetaNameValue, Pat, PatOr, PatPath,
-    PatStruct, PatTupleStruct, Token, parse_macro_input, punctuated::Punctuated, spanned::Spanned,
+    Attribute, Fields, Item, ItemEnum, Meta, MetaNameValue, Pat, PatTupleStruct, Token,
+    parse_macro_input, punctuated::Punctuated, spanned::Spanned,
 };
 
 #[proc_macro_attribute]
 pub fn nestum(args: TokenStream, input: TokenStream) -> TokenStream {
     let item = parse_macro_input!(input as Item);
     match item {
-        Item::Enum(item_enum) => {
+        Item::Enum(mut item_enum) => {
+            let metas = match Punctuated::<Meta, Token![,]>::parse_terminated.parse(args.clone()) {
+                Ok(metas) => metas,
+                Err(err) => return err.to_compile_error().into(),
+            };
+            let args = proc_macro2::TokenStream::from(args);
+            EnumOptions::parse(&item_enum.ident, &metas)
+                .and_then(|options| {
+                    // Kept on the enum so its options survive a re-run by `__nestum_expand!`.
+                    item_enum.attrs.push(syn::parse_quote!(#[nestum(#args)]));
+                    let resolver = Resolver::new(CallSite::Item(&Item::Enum(item_enum.clone())))?;
+                    expand_enum(item_enum, options, resolver)
+                })
+                .unwrap_or_else(|err| err.to_compile_error())
+                .into()
+        }
+        Item::Fn(_)
+        | Item::Impl(_)
+        | Item::Mod(syn::ItemMod {
+            content: Some(_), ..
+        }) => {
             if !args.is_empty() {
                 return syn::Error::new(
-                    item_enum.ident.span(),
-                    format!(
-                        "invalid #[nestum(...)] on enum {}; \
-nestum does not accept arguments. Use #[nestum] on enums only",
-                        item_enum.ident
-                    ),
+                    proc_macro2::TokenStream::from(args).span(),
+                    "#[nestum] on functions, impl blocks and modules does not accept arguments",
                 )
                 .to_compile_error()
                 .into();
             }
-            expand_enum(item_enum)
+            Resolver::new(CallSite::Item(&item))
+                .and_then(|resolver| expand_nested_item(item, resolver))
                 .unwrap_or_else(|err| err.to_compile_error())
                 .into()
         }
-        other => syn::Error::new(other.span(), "nestum can only be applied to enums")
-            .to_compile_error()
-            .into(),
+        other => syn::Error::new(
+            other.span(),
+            "nestum can only be applied to enums, functions, impl blocks and inline modules",
+        )
+        .to_compile_error()
+        .into(),
     }
 }
 
 #[proc_macro]
 pub fn nestum_match(input: TokenStream) -> TokenStream {
-    let expr = parse_macro_input!(input as ExprMatch);
-    expand_match(expr)
+    let tokens = proc_macro2::TokenStream::from(input.clone());
+    let input = parse_macro_input!(input as NestedInput);
+    Resolver::new(CallSite::Macro(&["nested", "nestum_match"], tokens.clone()))
+        .and_then(|resolver| expand_nested(input, tokens, resolver))
         .unwrap_or_else(|err| err.to_compile_error())
         .into()
 }
@@ -49,95 +71,724 @@ pub fn nested(input: TokenStream) -> TokenStream {
     nestum_match(input)
 }
 
-fn expand_enum(item: ItemEnum) -> Result<proc_macro2::TokenStream, syn::Error> {
-    let (file_path, module_root, module_path) = current_module_context()?;
-    let mut cache: HashMap<String, HashMap<String, ItemEnum>> = HashMap::new();
-    let all = collect_enums_by_module_path(&file_path, &module_root, &file_path)?;
-    for (module, enums) in all.into_iter() {
-        cache.insert(module, enums);
-    }
-    let enums_by_ident = cache.get(&module_path).cloned().ok_or_else(|| {
-        syn::Error::new(
+#[proc_macro]
+pub fn nested_matches(input: TokenStream) -> TokenStream {
+    let tokens = proc_macro2::TokenStream::from(input.clone());
+    let input = parse_macro_input!(input as MatchesInput);
+    Resolver::new(CallSite::Macro(&["nested_matches"], tokens.clone()))
+        .and_then(|resolver| expand_nested_matches(input, tokens, resolver))
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Runs a macro call again with the descriptors of enums exported by other crates. The calls are
+/// generated by nestum: each exported enum's descriptor macro forwards to this one.
+#[doc(hidden)]
+#[proc_macro]
+pub fn __nestum_expand(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as Reentry);
+    input
+        .expand()
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+fn expand_enum(
+    item: ItemEnum,
+    options: EnumOptions,
+    mut resolver: Resolver,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let module_path = resolver.current_module.clone();
+    if resolver.current_enums().is_none() {
+        return Err(syn::Error::new(
             proc_macro2::Span::call_site(),
             "no enums found for current module path; \
 ensure the enum is defined in the same source file and module as the macro call",
-        )
-    })?;
+        ));
+    }
+
+    let expanded = expand_enum_with_context(item.clone(), &options, &module_path, &mut resolver);
+    if let Some(request) = resolver.descriptor_request(quote!(enum_item { #item })) {
+        return Ok(request);
+    }
+    expanded
+}
+
+/// The input of `__nestum_expand!`: where the original call sits, what it was, and the
+/// descriptors received so far.
+struct Reentry {
+    file: syn::LitStr,
+    line: syn::LitInt,
+    kind: syn::Ident,
+    tokens: proc_macro2::TokenStream,
+    descriptors: Vec<Descriptor>,
+}
+
+impl syn::parse::Parse for Reentry {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let source: syn::Ident = input.parse()?;
+        if source != "source" {
+            return Err(syn::Error::new(source.span(), "expected `source`"));
+        }
+        input.parse::<Token![=]>()?;
+        let location;
+        syn::parenthesized!(location in input);
+        let file = location.parse()?;
+        location.parse::<Token![,]>()?;
+        let line = location.parse()?;
+        let kind = input.parse()?;
+        let tokens;
+        syn::braced!(tokens in input);
+        let tokens = tokens.parse()?;
+        let mut descriptors = Vec::new();
+        while !input.is_empty() {
+            descriptors.push(input.parse()?);
+        }
+        Ok(Reentry {
+            file,
+            line,
+            kind,
+            tokens,
+            descriptors,
+        })
+    }
+}
+
+impl Reentry {
+    fn expand(self) -> Result<proc_macro2::TokenStream, syn::Error> {
+        let mut resolver = Resolver::at(self.file.value(), self.line.base10_parse()?)?;
+        for descriptor in self.descriptors {
+            resolver.add_descriptor(descriptor);
+        }
+        let tokens = self.tokens;
+        match self.kind.to_string().as_str() {
+            "enum_item" => {
+                let item: ItemEnum = syn::parse2(tokens)?;
+                let options = EnumOptions::from_attrs(&item)?;
+                expand_enum(item, options, resolver)
+            }
+            "item" => expand_nested_item(syn::parse2(tokens)?, resolver),
+            "nested" => expand_nested(syn::parse2(tokens.clone())?, tokens, resolver),
+            "matches" => expand_nested_matches(syn::parse2(tokens.clone())?, tokens, resolver),
+            _ => Err(syn::Error::new(self.kind.span(), "unknown macro call")),
+        }
+    }
+}
+
+/// The enums another crate exported with `#[nestum(export)]`: `descriptor path { ... }`, where
+/// `path` is the external path that asked for them and each entry is `["module"] { enum }`. The
+/// exported enum comes first, followed by every enum nested below it.
+struct Descriptor {
+    path: syn::Path,
+    enums: Vec<(String, ItemEnum)>,
+}
+
+impl syn::parse::Parse for Descriptor {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let keyword: syn::Ident = input.parse()?;
+        if keyword != "descriptor" {
+            return Err(syn::Error::new(keyword.span(), "expected `descriptor`"));
+        }
+        let path = syn::Path::parse_mod_style(input)?;
+        let content;
+        syn::braced!(content in input);
+        let mut enums = Vec::new();
+        while !content.is_empty() {
+            let module;
+            syn::bracketed!(module in content);
+            let module: syn::LitStr = module.parse()?;
+            let item;
+            syn::braced!(item in content);
+            enums.push((module.value(), item.parse()?));
+        }
+        Ok(Descriptor { path, enums })
+    }
+}
+
+impl quote::ToTokens for Descriptor {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let path = &self.path;
+        let modules = self.enums.iter().map(|(module, _)| module);
+        let items = self.enums.iter().map(|(_, item)| item);
+        tokens.extend(quote!(descriptor #path { #([#modules] { #items })* }));
+    }
+}
+
+/// The body of `nested! { ... }`: a `match`, `if let`, `while let` or `matches!` expression, or
+/// statements such as `let ... else`.
+struct NestedInput {
+    stmts: Vec<syn::Stmt>,
+}
+
+impl syn::parse::Parse for NestedInput {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        Ok(NestedInput {
+            stmts: syn::Block::parse_within(input)?,
+        })
+    }
+}
+
+/// The arguments of `matches!`: `expression, pattern` with an optional `if` guard.
+struct MatchesInput {
+    expr: syn::Expr,
+    pat: Pat,
+    guard: Option<syn::Expr>,
+}
+
+impl syn::parse::Parse for MatchesInput {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let expr = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let pat = Pat::parse_multi_with_leading_vert(input)?;
+        let guard = if input.peek(Token![if]) {
+            input.parse::<Token![if]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+        }
+        Ok(MatchesInput { expr, pat, guard })
+    }
+}
+
+impl quote::ToTokens for MatchesInput {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let expr = &self.expr;
+        let pat = &self.pat;
+        tokens.extend(quote!(#expr, #pat));
+        if let Some(guard) = &self.guard {
+            tokens.extend(quote!(if #guard));
+        }
+    }
+}
+
+/// The arguments of a standard macro that takes expressions, e.g. `assert_eq!(a, b, "{}", c)`,
+/// `vec![a, b]` or `vec![a; n]`.
+struct MacroArgs {
+    args: Punctuated<syn::Expr, Token![,]>,
+    repeat: Option<syn::Expr>,
+}
+
+impl syn::parse::Parse for MacroArgs {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut args = Punctuated::new();
+        if input.is_empty() {
+            return Ok(MacroArgs { args, repeat: None });
+        }
+        args.push_value(input.parse()?);
+        if input.peek(Token![;]) {
+            input.parse::<Token![;]>()?;
+            let repeat = Some(input.parse()?);
+            return Ok(MacroArgs { args, repeat });
+        }
+        while !input.is_empty() {
+            args.push_punct(input.parse()?);
+            if input.is_empty() {
+                break;
+            }
+            args.push_value(input.parse()?);
+        }
+        Ok(MacroArgs { args, repeat: None })
+    }
+}
+
+impl quote::ToTokens for MacroArgs {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        self.args.to_tokens(tokens);
+        if let Some(repeat) = &self.repeat {
+            tokens.extend(quote!(; #repeat));
+        }
+    }
+}
+
+fn expand_nested(
+    input: NestedInput,
+    tokens: proc_macro2::TokenStream,
+    mut resolver: Resolver,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let mut stmts = input.stmts;
+    let mut rewriter = NestedRewriter::new(&mut resolver);
+    rewriter.rewrite_stmts(&mut stmts);
+    let finished = rewriter.finish();
+    if let Some(request) = resolver.descriptor_request(quote!(nested { #tokens })) {
+        return Ok(request);
+    }
+    finished?;
+
+    // A lone expression stays an expression so `nested!` can be used in expression position.
+    if let [syn::Stmt::Expr(expr, None)] = stmts.as_slice() {
+        return Ok(quote!(#expr));
+    }
+    Ok(quote!(#(#stmts)*))
+}
+
+/// `#[nestum]` on a function, impl block or inline module: rewrites every pattern inside it as
+/// if each were wrapped in `nested!`.
+fn expand_nested_item(
+    item: Item,
+    mut resolver: Resolver,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let mut rewritten = item.clone();
+    let mut rewriter = NestedRewriter::new(&mut resolver);
+    match &mut rewritten {
+        // The attribute's call site lies inside the module, so the resolver already starts there.
+        Item::Mod(module) => syn::visit_mut::visit_item_mod_mut(&mut rewriter, module),
+        other => rewriter.visit_item_mut(other),
+    }
+    let finished = rewriter.finish();
+    if let Some(request) = resolver.descriptor_request(quote!(item { #item })) {
+        return Ok(request);
+    }
+    finished?;
+    Ok(quote!(#rewritten))
+}
+
+fn expand_nested_matches(
+    input: MatchesInput,
+    tokens: proc_macro2::TokenStream,
+    mut resolver: Resolver,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let mut rewriter = NestedRewriter::new(&mut resolver);
+    let expr = rewriter.matches_expr(input);
+    let finished = rewriter.finish();
+    if let Some(request) = resolver.descriptor_request(quote!(matches { #tokens })) {
+        return Ok(request);
+    }
+    finished?;
+    let expr = expr?;
+    Ok(quote!(#expr))
+}
+
+/// Rewrites every pattern reachable from the visited syntax: match arms, `if let`, `while let`,
+/// `let` statements, closure parameters and `matches!` arguments, including those nested in arm
+/// bodies and guards. Errors are collected so one pass reports all of them.
+///
+/// Patterns that descend through a smart pointer cannot be expressed directly (there are no
+/// stable box patterns), so `if let`, `while let`, `let` and `matches!` using them are lowered to
+/// a `match` whose arms bind the pointer and match the pointee in the guard and body.
+struct NestedRewriter<'a> {
+    resolver: &'a mut Resolver,
+    error: Option<syn::Error>,
+}
+
+impl<'a> NestedRewriter<'a> {
+    fn new(resolver: &'a mut Resolver) -> Self {
+        NestedRewriter {
+            resolver,
+            error: None,
+        }
+    }
+
+    fn push_error(&mut self, err: syn::Error) {
+        match &mut self.error {
+            Some(existing) => existing.combine(err),
+            None => self.error = Some(err),
+        }
+    }
+
+    fn finish(self) -> Result<(), syn::Error> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_matches_input(&mut self, input: &mut MatchesInput) {
+        self.visit_expr_mut(&mut input.expr);
+        if let Some(guard) = &mut input.guard {
+            self.visit_expr_mut(guard);
+        }
+    }
+
+    /// `matches!(expr, pat if guard)` as an expression: `::core::matches!` with the pattern
+    /// rewritten, or a `match` when the pattern goes through a pointer.
+    fn matches_expr(&mut self, mut input: MatchesInput) -> Result<syn::Expr, syn::Error> {
+        match self.rewrite_matches_input(&mut input)? {
+            Some(expr) => Ok(expr),
+            None => Ok(syn::parse_quote!(::core::matches!(#input))),
+        }
+    }
+
+    /// Rewrites the arguments of `matches!` in place, or returns a `match` evaluating to a bool
+    /// when the pattern goes through a pointer, since only a guard can look behind it.
+    fn rewrite_matches_input(
+        &mut self,
+        input: &mut MatchesInput,
+    ) -> Result<Option<syn::Expr>, syn::Error> {
+        self.visit_matches_input(input);
+        let mut cases = rewrite_arm_pat(input.pat.clone(), self.resolver)?;
+        if let [(pat, pointers)] = cases.as_mut_slice()
+            && pointers.is_empty()
+        {
+            input.pat = pat.clone();
+            return Ok(None);
+        }
+        let arms = cases.into_iter().map(|(pat, pointers)| {
+            let guard = pointers
+                .guard(input.guard.clone())
+                .map(|guard| quote!(if #guard));
+            quote!(#pat #guard => true,)
+        });
+        let expr = &input.expr;
+        Ok(Some(syn::parse_quote! {
+            match #expr {
+                #(#arms)*
+                #[allow(unreachable_patterns)]
+                _ => false,
+            }
+        }))
+    }
+
+    fn rewrite_match(&mut self, expr_match: &mut syn::ExprMatch) -> Result<(), syn::Error> {
+        self.visit_expr_mut(&mut expr_match.expr);
+        // Arms through a pointer are guarded, so the exhaustiveness check does not count them;
+        // the other arms must cover their values, e.g. with `Event::Documents(_)` or `_`.
+        let arms = std::mem::take(&mut expr_match.arms);
+        for mut arm in arms {
+            if let Some((_, guard)) = &mut arm.guard {
+                self.visit_expr_mut(guard);
+            }
+            self.visit_expr_mut(&mut arm.body);
+
+            let guard = arm.guard.take().map(|(_, guard)| *guard);
+            let cases = rewrite_arm_pat(arm.pat.clone(), self.resolver)?;
+            for (pat, pointers) in cases {
+                expr_match.arms.push(syn::Arm {
+                    attrs: arm.attrs.clone(),
+                    pat,
+                    guard: pointers
+                        .guard(guard.clone())
+                        .map(|guard| (Default::default(), Box::new(guard))),
+                    fat_arrow_token: arm.fat_arrow_token,
+                    body: Box::new(pointers.body((*arm.body).clone())),
+                    comma: arm.comma,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrites `if let`; returns a replacement `match` when the pattern goes through a pointer.
+    fn rewrite_if_let(
+        &mut self,
+        expr_if: &mut syn::ExprIf,
+    ) -> Result<Option<syn::Expr>, syn::Error> {
+        let syn::Expr::Let(expr_let) = &mut *expr_if.cond else {
+            return Ok(None);
+        };
+        self.visit_expr_mut(&mut expr_let.expr);
+        self.visit_block_mut(&mut expr_if.then_branch);
+        if let Some((_, else_branch)) = &mut expr_if.else_branch {
+            self.visit_expr_mut(else_branch);
+        }
+
+        let mut cases = rewrite_arm_pat((*expr_let.pat).clone(), self.resolver)?;
+        if let [(pat, pointers)] = cases.as_mut_slice()
+            && pointers.is_empty()
+        {
+            *expr_let.pat = pat.clone();
+            return Ok(None);
+        }
+
+        let scrutinee = &expr_let.expr;
+        let then_branch = syn::Expr::Block(syn::ExprBlock {
+            attrs: Vec::new(),
+            label: None,
+            block: expr_if.then_branch.clone(),
+        });
+        let arms = cases.into_iter().map(|(pat, pointers)| {
+            let guard = pointers.guard(None).map(|guard| quote!(if #guard));
+            let body = pointers.body(then_branch.clone());
+            quote!(#pat #guard => #body,)
+        });
+        let otherwise = match &expr_if.else_branch {
+            Some((_, else_branch)) => quote!(#else_branch),
+            None => quote!({}),
+        };
+        let attrs = &expr_if.attrs;
+        Ok(Some(syn::parse_quote! {
+            #(#attrs)*
+            match #scrutinee {
+                #(#arms)*
+                #[allow(unreachable_patterns)]
+                _ => #otherwise,
+            }
+        }))
+    }
+
+    /// Rewrites `while let`; returns a replacement `loop` when the pattern goes through a pointer.
+    fn rewrite_while_let(
+        &mut self,
+        expr_while: &mut syn::ExprWhile,
+    ) -> Result<Option<syn::Expr>, syn::Error> {
+        let syn::Expr::Let(expr_let) = &mut *expr_while.cond else {
+            return Ok(None);
+        };
+        self.visit_expr_mut(&mut expr_let.expr);
+        self.visit_block_mut(&mut expr_while.body);
+
+        let mut cases = rewrite_arm_pat((*expr_let.pat).clone(), self.resolver)?;
+        if let [(pat, pointers)] = cases.as_mut_slice()
+            && pointers.is_empty()
+        {
+            *expr_let.pat = pat.clone();
+            return Ok(None);
+        }
+
+        let scrutinee = &expr_let.expr;
+        let body = syn::Expr::Block(syn::ExprBlock {
+            attrs: Vec::new(),
+            label: None,
+            block: expr_while.body.clone(),
+        });
+        let arms = cases.into_iter().map(|(pat, pointers)| {
+            let guard = pointers.guard(None).map(|guard| quote!(if #guard));
+            let body = pointers.body(body.clone());
+            quote!(#pat #guard => #body,)
+        });
+        let attrs = &expr_while.attrs;
+        let label = &expr_while.label;
+        Ok(Some(syn::parse_quote! {
+            #(#attrs)*
+            #label loop {
+                match #scrutinee {
+                    #(#arms)*
+                    #[allow(unreachable_patterns)]
+                    _ => break,
+                }
+            }
+        }))
+    }
+
+    /// Rewrites the statements of a block. A `let` through a pointer expands into several
+    /// statements: the first binds the pointer itself, the rest destructure its pointee.
+    fn rewrite_stmts(&mut self, stmts: &mut Vec<syn::Stmt>) {
+        for stmt in std::mem::take(stmts) {
+            match stmt {
+                syn::Stmt::Local(mut local) => match self.rewrite_local(&mut local) {
+                    Ok(lets) => {
+                        stmts.push(syn::Stmt::Local(local));
+                        stmts.extend(lets);
+                    }
+                    Err(err) => self.push_error(err),
+                },
+                mut stmt => {
+                    self.visit_stmt_mut(&mut stmt);
+                    stmts.push(stmt);
+                }
+            }
+        }
+    }
 
-    let mut marked_enums = HashSet::new();
-    for (name, info) in enums_by_ident.iter() {
-        match nestum_attr_kind(&info.attrs)? {
-            NestumAttrKind::None => {}
-            NestumAttrKind::Empty => {
-                marked_enums.insert(name.clone());
+    /// Rewrites a `let` statement, returning the statements that must follow it.
+    fn rewrite_local(&mut self, local: &mut syn::Local) -> Result<Vec<syn::Stmt>, syn::Error> {
+        if let Some(init) = &mut local.init {
+            self.visit_expr_mut(&mut init.expr);
+            if let Some((_, diverge)) = &mut init.diverge {
+                self.visit_expr_mut(diverge);
             }
-            NestumAttrKind::WithArgs => {
+        }
+
+        let span = local.pat.span();
+        let pat = match local.pat.clone() {
+            Pat::Type(pat_type) => *pat_type.pat,
+            pat => pat,
+        };
+        let mut cases = rewrite_arm_pat(pat, self.resolver)?;
+        let (pat, pointers) = match cases.len() {
+            1 => cases.pop().expect("one case"),
+            _ => {
                 return Err(syn::Error::new(
-                    info.span(),
-                    format!(
-                        "invalid #[nestum(...)] on enum {name}; \
-nestum does not accept arguments. Use #[nestum] on enums only"
-                    ),
+                    span,
+                    "or-patterns through Box, Rc or Arc are not supported in let statements; \
+use a match instead",
                 ));
             }
+        };
+        match &mut local.pat {
+            Pat::Type(pat_type) => *pat_type.pat = pat,
+            other => *other = pat,
         }
-    }
+        if pointers.is_empty() {
+            return Ok(Vec::new());
+        }
+        let Some(init) = &local.init else {
+            return Err(pointer_context_error(span));
+        };
+        let Some((_, otherwise)) = &init.diverge else {
+            return Err(syn::Error::new(
+                span,
+                "let statements with nested patterns through Box, Rc or Arc cannot be checked \
+for exhaustiveness; add an else branch, e.g. `let ... else { return; };`",
+            ));
+        };
 
-    expand_enum_with_context(
-        item,
-        &enums_by_ident,
-        &marked_enums,
-        &module_path,
-        &file_path,
-        &module_root,
-        &mut cache,
-    )
+        // Each pointee is matched by its own `let ... else`, so the compiler still decides which
+        // idents bind and which name unit variants or constants.
+        Ok(pointers.lets(Some(otherwise)))
+    }
 }
 
-fn expand_match(expr: ExprMatch) -> Result<proc_macro2::TokenStream, syn::Error> {
-    let (file_path, module_root, module_path) = current_module_context()?;
-    let mut cache: HashMap<String, HashMap<String, ItemEnum>> = HashMap::new();
-    let all = collect_enums_by_module_path(&file_path, &module_root, &file_path)?;
-    for (module, enums) in all.into_iter() {
-        cache.insert(module, enums);
+impl VisitMut for NestedRewriter<'_> {
+    fn visit_pat_mut(&mut self, pat: &mut Pat) {
+        // `rewrite_pat` recurses through sub-patterns itself.
+        let span = pat.span();
+        let mut pointers = PointerMatches::default();
+        let result = rewrite_pat_in_place(pat, self.resolver, &mut pointers).and_then(|()| {
+            if pointers.is_empty() {
+                Ok(())
+            } else {
+                Err(pointer_context_error(span))
+            }
+        });
+        if let Err(err) = result {
+            self.push_error(err);
+        }
+    }
+
+    fn visit_item_mod_mut(&mut self, item: &mut syn::ItemMod) {
+        // Paths inside an inline module resolve relative to that module.
+        let parent = self.resolver.current_module.clone();
+        self.resolver.current_module = if parent == "crate" {
+            item.ident.to_string()
+        } else {
+            format!("{parent}::{}", item.ident)
+        };
+        syn::visit_mut::visit_item_mod_mut(self, item);
+        self.resolver.current_module = parent;
     }
-    let enums_by_ident = cache.get(&module_path).cloned().ok_or_else(|| {
-        syn::Error::new(
-            proc_macro2::Span::call_site(),
-            "no enums found for current module path; \
-ensure the enum is defined in the same source file and module as the macro call",
-        )
-    })?;
 
-    let mut arms = Vec::new();
-    for mut arm in expr.arms {
-        arm.pat = rewrite_pat(
-            arm.pat,
-            &file_path,
-            &module_root,
-            &module_path,
-            &enums_by_ident,
-            &mut cache,
-        )?;
-        arms.push(arm);
+    fn visit_block_mut(&mut self, block: &mut syn::Block) {
+        self.rewrite_stmts(&mut block.stmts);
     }
 
-    let expr_value = expr.expr;
-    Ok(quote! {
-        match #expr_value {
-            #(#arms),*
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        let result = match expr {
+            syn::Expr::Match(expr_match) => self.rewrite_match(expr_match).map(|()| None),
+            syn::Expr::If(expr_if) if matches!(*expr_if.cond, syn::Expr::Let(_)) => {
+                self.rewrite_if_let(expr_if)
+            }
+            syn::Expr::While(expr_while) if matches!(*expr_while.cond, syn::Expr::Let(_)) => {
+                self.rewrite_while_let(expr_while)
+            }
+            syn::Expr::Macro(expr_macro) if is_matches_macro(&expr_macro.mac) => {
+                match expr_macro.mac.parse_body::<MatchesInput>() {
+                    Ok(mut input) => self.rewrite_matches_input(&mut input).inspect(|lowered| {
+                        if lowered.is_none() {
+                            expr_macro.mac.tokens = quote!(#input);
+                        }
+                    }),
+                    Err(err) => Err(err),
+                }
+            }
+            syn::Expr::Struct(_) => {
+                syn::visit_mut::visit_expr_mut(self, expr);
+                let syn::Expr::Struct(expr_struct) = expr else {
+                    unreachable!()
+                };
+                rewrite_struct_expr(expr_struct, self.resolver)
+            }
+            _ => {
+                syn::visit_mut::visit_expr_mut(self, expr);
+                Ok(None)
+            }
+        };
+        match result {
+            Ok(Some(rewritten)) => *expr = rewritten,
+            Ok(None) => {}
+            Err(err) => self.push_error(err),
         }
-    })
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut syn::Macro) {
+        if is_expr_macro(mac) {
+            // Arguments that are not plain expressions are left to the macro.
+            if let Ok(mut input) = mac.parse_body::<MacroArgs>() {
+                for arg in input.args.iter_mut().chain(&mut input.repeat) {
+                    self.visit_expr_mut(arg);
+                }
+                mac.tokens = quote!(#input);
+            }
+            return;
+        }
+        if !is_matches_macro(mac) {
+            return;
+        }
+
+        match mac.parse_body::<MatchesInput>() {
+            Ok(mut input) => {
+                self.visit_matches_input(&mut input);
+                self.visit_pat_mut(&mut input.pat);
+                mac.tokens = quote!(#input);
+            }
+            Err(err) => self.push_error(err),
+        }
+    }
+}
+
+/// `matches!`, `core::matches!` or `std::matches!`; other macros named `matches` are left alone.
+fn is_matches_macro(mac: &syn::Macro) -> bool {
+    std_macro_name(mac).is_some_and(|name| name == "matches")
+}
+
+/// Standard macros whose arguments are expressions, which are rewritten like any other.
+const EXPR_MACROS: &[&str] = &[
+    "assert",
+    "assert_eq",
+    "assert_ne",
+    "debug_assert",
+    "debug_assert_eq",
+    "debug_assert_ne",
+    "vec",
+    "format",
+    "print",
+    "println",
+    "eprint",
+    "eprintln",
+    "write",
+    "writeln",
+    "panic",
+];
+
+fn is_expr_macro(mac: &syn::Macro) -> bool {
+    std_macro_name(mac).is_some_and(|name| EXPR_MACROS.iter().any(|known| name == known))
+}
+
+/// The name of a macro called by its own name or through `core`, `alloc` or `std`.
+fn std_macro_name(mac: &syn::Macro) -> Option<&syn::Ident> {
+    let segments: Vec<_> = mac.path.segments.iter().map(|s| &s.ident).collect();
+    match segments.as_slice() {
+        [name] if mac.path.leading_colon.is_none() => Some(name),
+        [krate, name] if *krate == "core" || *krate == "alloc" || *krate == "std" => Some(name),
+        _ => None,
+    }
+}
+
+fn pointer_context_error(span: proc_macro2::Span) -> syn::Error {
+    syn::Error::new(
+        span,
+        "nested patterns through Box, Rc or Arc are only supported in match arms, \
+if let, while let, let statements and matches!",
+    )
+}
+
+fn rewrite_pat_in_place(
+    pat: &mut Pat,
+    resolver: &mut Resolver,
+    pointers: &mut PointerMatches,
+) -> Result<(), syn::Error> {
+    let original = std::mem::replace(pat, Pat::Verbatim(Default::default()));
+    *pat = rewrite_pat(original, resolver, pointers)?;
+    Ok(())
 }
 
 fn expand_enum_with_context(
     item: ItemEnum,
-    enums_by_ident: &HashMap<String, ItemEnum>,
-    marked_enums: &HashSet<String>,
+    options: &EnumOptions,
     module_path: &str,
-    current_file: &str,
-    module_root: &std::path::Path,
-    cache: &mut HashMap<String, HashMap<String, ItemEnum>>,
+    resolver: &mut Resolver,
 ) -> Result<proc_macro2::TokenStream, syn::Error> {
     let vis = item.vis.clone();
     let enum_ident = item.ident.clone();
@@ -154,7 +805,6 @@ fn expand_enum_with_context(
     let mut nested_variant_modules = Vec::new();
 
     for variant in item.variants.iter() {
-        let external_path = parse_variant_external_path(&variant.attrs)?;
         let mut cleaned_attrs = Vec::new();
         for attr in &variant.attrs {
             if !attr.path().is_ident("nestum") {
@@ -164,234 +814,320 @@ fn expand_enum_with_context(
 
         let mut variant_clean = variant.clone();
         variant_clean.attrs = cleaned_attrs;
+        for field in variant_clean.fields.iter_mut() {
+            field.attrs.retain(|attr| !attr.path().is_ident("nestum"));
+        }
 
-        if let Some(external_path) = external_path {
-            let inner_ty = extract_single_tuple_type(variant).map_err(|_| {
-                syn::Error::new(
-                    variant.span(),
-                    format!(
-                        "variant {}::{} uses #[nestum(external = \"...\")], \
-but is not a single-field tuple variant",
-                        enum_ident, variant.ident
-                    ),
-                )
-            })?;
-
-            let inner_ident = extract_simple_ident(&inner_ty).map_err(|_| {
-                syn::Error::new(
-                    inner_ty.span(),
-                    "nested enum type must be a simple ident when using #[nestum(external = \"...\")]; \
-use a bare enum name in the field",
-                )
-            })?;
+        if let Some(child) = resolver.nested_child(&item, options, module_path, variant)? {
+            rewrite_variant_type_for_nested(&mut variant_clean, &child)?;
+
+            let chain = [ChainLevel::new(
+                quote! { #enum_ident },
+                &child,
+                &GenericSubst::identity(),
+            )];
+            let mut visited = vec![(module_path.to_string(), enum_ident.to_string())];
+            nested_variant_modules.push(build_nested_module(
+                &item,
+                &chain,
+                &child,
+                &GenericSubst::identity(),
+                &mut visited,
+                resolver,
+            )?);
+        }
 
-            let external_ident = external_path
-                .segments
-                .last()
-                .map(|s| s.ident.clone())
-                .ok_or_else(|| {
-                    syn::Error::new(
-                        external_path.span(),
-                        "external path must include an enum ident",
-                    )
-                })?;
+        enum_variants.push(variant_clean);
+    }
 
-            if inner_ident != external_ident {
-                return Err(syn::Error::new(
-                    inner_ty.span(),
-                    format!(
-                        "field type {} does not match external enum path {}; \
-use the enum ident as the field type",
-                        inner_ident,
-                        external_path_to_string(&external_path),
-                    ),
-                ));
+    let conversions = build_conversions(&item, options, module_path, resolver)?;
+    let accessors = build_accessors(&item, options, module_path, resolver)?;
+    let tree = Tree::collect(&item, options, module_path, resolver)?;
+    let (kind_enum, kind_impl) = build_kind(&item, options, &tree)?;
+    let paths = build_paths(&item, options, &tree);
+    let values = build_values(&item, options, &tree)?;
+    let (string_items, string_impls) = build_strings(&item, options, &tree)?;
+    let serde = build_serde(&item, options, &tree)?;
+    let (visitor_traits, visitor_methods) = build_visitor(&item, options, &tree);
+    let selector = build_selector(&item, options, &tree)?;
+    let set = build_set(&item, options, &tree)?;
+    let export = build_export(&item, options, module_path, &tree);
+    let generics = &item.generics;
+    let where_clause = &item.generics.where_clause;
+
+    if let Some(companion) = &options.companion {
+        return Ok(quote! {
+            #(#enum_attrs)*
+            #vis enum #enum_ident #generics #where_clause {
+                #(#enum_variants),*
             }
 
-            let (inner_enum, inner_is_marked) =
-                resolve_external_enum(&external_path, current_file, module_root, cache)?
-                    .ok_or_else(|| {
-                        syn::Error::new(
-                            external_path.span(),
-                            format!(
-                                "external enum {} not found; \
-ensure the module path exists and the enum is declared in that module",
-                                external_path_to_string(&external_path),
-                            ),
-                        )
-                    })?;
-
-            if !inner_is_marked {
-                return Err(syn::Error::new(
-                    external_path.span(),
-                    "external enum must be marked with #[nestum] to enable nesting",
-                ));
+            #conversions
+            #accessors
+            #kind_impl
+            #paths
+            #values
+            #string_impls
+            #serde
+            #visitor_methods
+            #kind_enum
+            #string_items
+            #visitor_traits
+            #selector
+            #set
+            #export
+
+            #vis mod #companion {
+                #[allow(unused_imports)]
+                use super::*;
+
+                #(#nested_variant_modules)*
             }
-
-            let enum_type_path =
-                enum_type_path_from_external_path(&external_path, &inner_enum.ident, true);
-            rewrite_variant_type_for_nested(&mut variant_clean, enum_type_path)?;
-
-            let variant_ident = &variant.ident;
-            let wrapper_items = build_wrappers_with_path(
-                &enum_ident,
-                variant_ident,
-                &inner_enum,
-                inner_is_marked,
-                &external_path,
-            )?;
-
-            nested_variant_modules.push(quote! {
-                #[allow(non_snake_case)]
-                pub mod #variant_ident {
-                    #(#wrapper_items)*
-                }
-            });
-        } else if let Ok(inner_ty) = extract_single_tuple_type(variant) {
-            if let Ok(inner_ident) = extract_simple_ident(&inner_ty) {
-                if let Some(inner_enum) = enums_by_ident.get(&inner_ident.to_string()) {
-                    let inner_is_marked = marked_enums.contains(&inner_ident.to_string());
-                    if inner_is_marked {
-                        let enum_type_path =
-                            enum_type_path_from_module(module_path, &inner_ident, true);
-                        rewrite_variant_type_for_nested(&mut variant_clean, enum_type_path)?;
-
-                        let variant_ident = &variant.ident;
-                        let wrapper_items = build_wrappers(
-                            &enum_ident,
-                            variant_ident,
-                            inner_enum,
-                            inner_is_marked,
-                        )?;
-
-                        nested_variant_modules.push(quote! {
-                            #[allow(non_snake_case)]
-                            pub mod #variant_ident {
-                                #(#wrapper_items)*
-                            }
-                        });
-                    }
-                }
-            }
-        }
-
-        enum_variants.push(variant_clean);
-    }
+        });
+    }
 
     Ok(quote! {
         #[allow(non_snake_case)]
         #vis mod #enum_mod_ident {
+            #[allow(unused_imports)]
+            use super::*;
+
             #(#enum_attrs)*
-            #vis enum #enum_ident {
+            #vis enum #enum_ident #generics #where_clause {
                 #(#enum_variants),*
             }
 
+            #conversions
+            #accessors
+            #kind_impl
+            #paths
+            #values
+            #string_impls
+            #serde
+            #visitor_methods
+
             #(#nested_variant_modules)*
         }
+
+        #kind_enum
+        #string_items
+        #visitor_traits
+        #selector
+        #set
+        #export
     })
 }
 
-fn build_wrappers(
-    outer_enum: &syn::Ident,
-    outer_variant: &syn::Ident,
-    inner_enum: &ItemEnum,
-    inner_is_marked: bool,
-) -> Result<Vec<proc_macro2::TokenStream>, syn::Error> {
-    let inner_enum_ident = &inner_enum.ident;
-    let mut items = Vec::new();
-    for inner_variant in inner_enum.variants.iter() {
-        let inner_ident = &inner_variant.ident;
-        let inner_variant_path = if inner_is_marked {
-            quote! { super::super::#inner_enum_ident::#inner_enum_ident::#inner_ident }
-        } else {
-            quote! { super::super::#inner_enum_ident::#inner_ident }
+/// A variant with a field holding another `#[nestum]` enum, possibly behind a smart pointer.
+#[derive(Clone)]
+struct NestedChild {
+    variant: syn::Ident,
+    /// The inner enum type as written in the field, without the pointer.
+    field_ty: syn::Type,
+    enum_item: ItemEnum,
+    module_path: String,
+    pointer: Option<Pointer>,
+    /// All fields of the variant; the one at `index` holds the inner enum, the rest are context.
+    fields: Fields,
+    index: usize,
+}
+
+impl NestedChild {
+    /// Absolute path to the inner enum type.
+    fn enum_path(&self) -> syn::Path {
+        enum_type_path(&self.module_path, &self.enum_item)
+    }
+}
+
+/// A smart pointer between a nested variant and its inner enum, e.g. `Box<Inner>`.
+#[derive(Clone)]
+struct Pointer {
+    /// Path used in generated code; `Box`, `Rc` and `Arc` are spelled out through `std`.
+    path: syn::Path,
+}
+
+impl Pointer {
+    /// Splits `Box<Inner>`, `Rc<Inner>`, `Arc<Inner>` or one of the enum's configured pointer
+    /// types into the pointer and `Inner`.
+    fn split(ty: &syn::Type, options: &EnumOptions) -> Option<(Pointer, syn::Type)> {
+        let syn::Type::Path(type_path) = ty else {
+            return None;
         };
+        let last = type_path.path.segments.last()?;
+        let syn::PathArguments::AngleBracketed(args) = &last.arguments else {
+            return None;
+        };
+        let (1, Some(syn::GenericArgument::Type(inner))) = (args.args.len(), args.args.first())
+        else {
+            return None;
+        };
+        let path: syn::Path = match last.ident.to_string().as_str() {
+            "Box" => syn::parse_quote!(::std::boxed::Box),
+            "Rc" => syn::parse_quote!(::std::rc::Rc),
+            "Arc" => syn::parse_quote!(::std::sync::Arc),
+            _ => options
+                .pointers
+                .iter()
+                .find(|pointer| pointer.segments.last().map(|s| &s.ident) == Some(&last.ident))?
+                .clone(),
+        };
+        Some((Pointer { path }, inner.clone()))
+    }
 
-        match &inner_variant.fields {
-            Fields::Unit => {
-                items.push(quote! {
-                    pub const #inner_ident: super::#outer_enum =
-                        super::#outer_enum::#outer_variant(#inner_variant_path);
-                });
-            }
-            Fields::Unnamed(fields) => {
-                let args: Vec<_> = fields
-                    .unnamed
-                    .iter()
-                    .enumerate()
-                    .map(|(i, f)| {
-                        let ident = format_ident!("v{i}");
-                        let ty = &f.ty;
-                        quote! { #ident: #ty }
-                    })
-                    .collect();
-                let arg_idents: Vec<_> = fields
-                    .unnamed
-                    .iter()
-                    .enumerate()
-                    .map(|(i, _)| {
-                        let ident = format_ident!("v{i}");
-                        quote! { #ident }
-                    })
-                    .collect();
-                items.push(quote! {
-                    pub fn #inner_ident(#(#args),*) -> super::#outer_enum {
-                        super::#outer_enum::#outer_variant(#inner_variant_path(#(#arg_idents),*))
-                    }
-                });
-            }
-            Fields::Named(fields) => {
-                let args: Vec<_> = fields
-                    .named
-                    .iter()
-                    .map(|f| {
-                        let ident = f.ident.as_ref().unwrap();
-                        let ty = &f.ty;
-                        quote! { #ident: #ty }
-                    })
-                    .collect();
-                let arg_idents: Vec<_> = fields
-                    .named
-                    .iter()
-                    .map(|f| {
-                        let ident = f.ident.as_ref().unwrap();
-                        quote! { #ident }
-                    })
-                    .collect();
-                items.push(quote! {
-                    pub fn #inner_ident(#(#args),*) -> super::#outer_enum {
-                        super::#outer_enum::#outer_variant(#inner_variant_path { #(#arg_idents),* })
-                    }
-                });
-            }
+    fn is_box(&self) -> bool {
+        self.path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Box")
+    }
+
+    /// `written` (the pointer type as it appears in the field) around `inner` instead.
+    fn rewrap_type(written: &syn::Type, inner: syn::Type) -> syn::Type {
+        let mut ty = written.clone();
+        if let syn::Type::Path(type_path) = &mut ty
+            && let Some(last) = type_path.path.segments.last_mut()
+        {
+            last.arguments = syn::PathArguments::AngleBracketed(syn::parse_quote!(<#inner>));
         }
+        ty
     }
 
-    Ok(items)
+    fn wrap_expr(&self, expr: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let path = &self.path;
+        quote!(#path::new(#expr))
+    }
 }
 
-fn build_wrappers_with_path(
-    outer_enum: &syn::Ident,
-    outer_variant: &syn::Ident,
-    inner_enum: &ItemEnum,
-    inner_is_marked: bool,
-    inner_path: &syn::Path,
-) -> Result<Vec<proc_macro2::TokenStream>, syn::Error> {
-    let inner_enum_ident = &inner_enum.ident;
+/// One nested variant on the way from the root enum down to a generated constructor.
+#[derive(Clone)]
+struct ChainLevel {
+    /// The enum declaring the variant: relative to the shadow or companion module at the root,
+    /// absolute below it.
+    enum_path: proc_macro2::TokenStream,
+    variant: syn::Ident,
+    pointer: Option<Pointer>,
+    fields: Fields,
+    index: usize,
+    /// Types of the variant's context fields, in terms of the root enum's generic parameters.
+    context_tys: Vec<syn::Type>,
+}
+
+impl ChainLevel {
+    /// `subst` maps the generic parameters of the enum declaring `child` onto the root's.
+    fn new(enum_path: proc_macro2::TokenStream, child: &NestedChild, subst: &GenericSubst) -> Self {
+        ChainLevel {
+            enum_path,
+            variant: child.variant.clone(),
+            pointer: child.pointer.clone(),
+            fields: child.fields.clone(),
+            index: child.index,
+            context_tys: context_fields(&child.fields, child.index)
+                .into_iter()
+                .map(|field| subst.apply(&field.ty))
+                .collect(),
+        }
+    }
+}
+
+/// Builds the wrapper module for one nested variant, recursing into the inner enum's own
+/// nested variants so `Outer::A::B::Leaf` constructs the outermost value at any depth.
+///
+/// `chain` lists each level from the root down to `child`, and `parent_subst` maps the parent
+/// enum's generic parameters onto the root's. The constructors are generic over the root enum's
+/// parameters and take every level's context fields before the leaf's own; unit leaves are
+/// consts when there are no parameters, context fields or pointers.
+/// Enums already in `visited` are not expanded again, which keeps recursive trees finite.
+fn build_nested_module(
+    root: &ItemEnum,
+    chain: &[ChainLevel],
+    child: &NestedChild,
+    parent_subst: &GenericSubst,
+    visited: &mut Vec<(String, String)>,
+    resolver: &mut Resolver,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let root_ident = &root.ident;
+    let inner_enum = &child.enum_item;
+    let applied_ty = parent_subst.apply(&child.field_ty);
+    let subst = GenericSubst::new(&inner_enum.generics, &applied_ty)?;
+    let fn_generics = constructor_generics(&root.generics);
+    let (_, ty_generics, where_clause) = root.generics.split_for_impl();
+    let has_generics = !root.generics.params.is_empty();
+
+    let supers: Vec<_> = (0..chain.len()).map(|_| quote! { super:: }).collect();
+    let root_ty = quote! { #(#supers)* #root_ident #ty_generics };
+    let inner_path = child.enum_path();
+    let mut context_idents = Vec::new();
+    let mut context_args = Vec::new();
+    for level in chain {
+        let mut idents = Vec::new();
+        for ty in &level.context_tys {
+            let ident = format_ident!("c{}", context_args.len());
+            context_args.push(quote! { #ident: #ty });
+            idents.push(quote! { #ident });
+        }
+        context_idents.push(idents);
+    }
+    let wrap = |leaf: proc_macro2::TokenStream| {
+        chain.iter().zip(&context_idents).enumerate().rev().fold(
+            leaf,
+            |expr, (depth, (level, context))| {
+                let expr = match &level.pointer {
+                    Some(pointer) => pointer.wrap_expr(expr),
+                    None => expr,
+                };
+                let enum_path = &level.enum_path;
+                let variant = &level.variant;
+                let path = if depth == 0 {
+                    quote! { #(#supers)* #enum_path::#variant }
+                } else {
+                    quote! { #enum_path::#variant }
+                };
+                nested_variant_tokens(path, &level.fields, level.index, context.clone(), expr)
+            },
+        )
+    };
+    // Allocating a pointer is not const, so unit leaves below one are functions.
+    let const_units = !has_generics
+        && context_args.is_empty()
+        && chain.iter().all(|level| level.pointer.is_none());
+
+    let inner_options = EnumOptions::from_attrs(inner_enum)?;
+    let mut grandchildren = HashMap::new();
+    for inner_variant in inner_enum.variants.iter() {
+        if let Some(grandchild) = resolver.nested_child(
+            inner_enum,
+            &inner_options,
+            &child.module_path,
+            inner_variant,
+        )? {
+            grandchildren.insert(inner_variant.ident.to_string(), grandchild);
+        }
+    }
+
     let mut items = Vec::new();
     for inner_variant in inner_enum.variants.iter() {
         let inner_ident = &inner_variant.ident;
-        let inner_variant_path = if inner_is_marked {
-            quote! { #inner_path::#inner_enum_ident::#inner_ident }
-        } else {
-            quote! { #inner_path::#inner_ident }
-        };
+        let inner_variant_path = quote! { #inner_path::#inner_ident };
+        let mut inner_variant = inner_variant.clone();
+        if let Some(grandchild) = grandchildren.get(&inner_ident.to_string()) {
+            rewrite_variant_type_for_nested(&mut inner_variant, grandchild)?;
+        }
 
         match &inner_variant.fields {
+            Fields::Unit if const_units => {
+                let value = wrap(inner_variant_path);
+                items.push(quote! {
+                    pub const #inner_ident: #root_ty = #value;
+                });
+            }
             Fields::Unit => {
+                let value = wrap(inner_variant_path);
                 items.push(quote! {
-                    pub const #inner_ident: super::#outer_enum =
-                        super::#outer_enum::#outer_variant(#inner_variant_path);
+                    pub fn #inner_ident #fn_generics(#(#context_args),*) -> #root_ty
+                    #where_clause
+                    {
+                        #value
+                    }
                 });
             }
             Fields::Unnamed(fields) => {
@@ -401,7 +1137,7 @@ fn build_wrappers_with_path(
                     .enumerate()
                     .map(|(i, f)| {
                         let ident = format_ident!("v{i}");
-                        let ty = &f.ty;
+                        let ty = subst.apply(&f.ty);
                         quote! { #ident: #ty }
                     })
                     .collect();
@@ -414,9 +1150,12 @@ fn build_wrappers_with_path(
                         quote! { #ident }
                     })
                     .collect();
+                let value = wrap(quote! { #inner_variant_path(#(#arg_idents),*) });
                 items.push(quote! {
-                    pub fn #inner_ident(#(#args),*) -> super::#outer_enum {
-                        super::#outer_enum::#outer_variant(#inner_variant_path(#(#arg_idents),*))
+                    pub fn #inner_ident #fn_generics(#(#context_args,)* #(#args),*) -> #root_ty
+                    #where_clause
+                    {
+                        #value
                     }
                 });
             }
@@ -426,7 +1165,7 @@ fn build_wrappers_with_path(
                     .iter()
                     .map(|f| {
                         let ident = f.ident.as_ref().unwrap();
-                        let ty = &f.ty;
+                        let ty = subst.apply(&f.ty);
                         quote! { #ident: #ty }
                     })
                     .collect();
@@ -438,761 +1177,3847 @@ fn build_wrappers_with_path(
                         quote! { #ident }
                     })
                     .collect();
+                let value = wrap(quote! { #inner_variant_path { #(#arg_idents),* } });
                 items.push(quote! {
-                    pub fn #inner_ident(#(#args),*) -> super::#outer_enum {
-                        super::#outer_enum::#outer_variant(#inner_variant_path { #(#arg_idents),* })
+                    pub fn #inner_ident #fn_generics(#(#context_args,)* #(#args),*) -> #root_ty
+                    #where_clause
+                    {
+                        #value
                     }
                 });
             }
         }
     }
 
-    Ok(items)
-}
+    visited.push((child.module_path.clone(), inner_enum.ident.to_string()));
+    for inner_variant in inner_enum.variants.iter() {
+        let Some(grandchild) = grandchildren.get(&inner_variant.ident.to_string()) else {
+            continue;
+        };
+        let key = (
+            grandchild.module_path.clone(),
+            grandchild.enum_item.ident.to_string(),
+        );
+        if visited.contains(&key) {
+            continue;
+        }
 
-fn rewrite_variant_type_for_nested(
-    variant: &mut syn::Variant,
-    type_path: syn::Path,
-) -> Result<(), syn::Error> {
-    let syn::Fields::Unnamed(fields) = &mut variant.fields else {
-        return Ok(());
-    };
-    if fields.unnamed.len() != 1 {
-        return Ok(());
+        let mut next_chain = chain.to_vec();
+        next_chain.push(ChainLevel::new(quote! { #inner_path }, grandchild, &subst));
+        items.push(build_nested_module(
+            root,
+            &next_chain,
+            grandchild,
+            &subst,
+            visited,
+            resolver,
+        )?);
     }
+    visited.pop();
 
-    let ty = syn::Type::Path(syn::TypePath {
-        qself: None,
-        path: type_path,
-    });
-    fields.unnamed[0].ty = ty;
-    Ok(())
+    let variant_ident = &child.variant;
+    Ok(quote! {
+        #[allow(non_snake_case)]
+        pub mod #variant_ident {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #(#items)*
+        }
+    })
 }
 
-fn enum_type_path_from_module(
-    module_path: &str,
-    enum_ident: &syn::Ident,
-    marked: bool,
-) -> syn::Path {
-    let module_idents = absolute_module_idents(module_path);
-    let mut tail = vec![enum_ident.clone()];
-    if marked {
-        tail.push(enum_ident.clone());
-    }
-    build_path_from_idents(module_idents, &tail)
+/// A conversion between the root enum and an enum nested below it, through `levels`.
+struct Conversion {
+    inner_ty: syn::Type,
+    levels: Vec<ConversionLevel>,
+    /// The root variant the conversion goes through, where an ambiguity is reported.
+    span: proc_macro2::Span,
 }
 
-fn enum_type_path_from_external_path(
-    base_path: &syn::Path,
-    enum_ident: &syn::Ident,
-    marked: bool,
-) -> syn::Path {
-    let mut path = base_path.clone();
-    if marked {
-        path.segments.push(syn::PathSegment {
-            ident: enum_ident.clone(),
-            arguments: syn::PathArguments::None,
-        });
-    }
-    path
+/// One variant on a conversion path, with the pointer around its inner enum.
+#[derive(Clone)]
+struct ConversionLevel {
+    variant: proc_macro2::TokenStream,
+    span: proc_macro2::Span,
+    pointer: Option<Pointer>,
+    /// The variant's fields, which hold nothing but the inner enum at `index`.
+    fields: Fields,
+    index: usize,
 }
 
-fn rewrite_pat(
-    pat: Pat,
-    current_file: &str,
-    module_root: &std::path::Path,
-    current_module: &str,
-    enums_by_ident: &HashMap<String, ItemEnum>,
-    cache: &mut HashMap<String, HashMap<String, ItemEnum>>,
-) -> Result<Pat, syn::Error> {
-    match pat {
-        Pat::Path(pat_path) => rewrite_pat_path(
-            pat_path,
-            current_file,
-            module_root,
-            current_module,
-            enums_by_ident,
-            cache,
-        ),
-        Pat::TupleStruct(pat_tuple) => rewrite_pat_tuple_struct(
-            pat_tuple,
-            current_file,
-            module_root,
-            current_module,
-            enums_by_ident,
-            cache,
-        ),
-        Pat::Struct(pat_struct) => rewrite_pat_struct(
-            pat_struct,
-            current_file,
-            module_root,
-            current_module,
-            enums_by_ident,
-            cache,
-        ),
-        Pat::Or(PatOr {
-            attrs,
-            leading_vert,
-            cases,
-        }) => {
-            let mut new_pats = Vec::with_capacity(cases.len());
-            for pat in cases {
-                new_pats.push(rewrite_pat(
-                    pat,
-                    current_file,
-                    module_root,
-                    current_module,
-                    enums_by_ident,
-                    cache,
-                )?);
-            }
-            Ok(Pat::Or(PatOr {
-                attrs,
-                leading_vert,
-                cases: Punctuated::from_iter(new_pats),
-            }))
-        }
-        other => Ok(other),
+/// `#[nestum(conversions)]`: `From<Inner> for Root` and `TryFrom<Root> for Inner` for every enum
+/// nested below `root`, at any depth, through variants without context fields. An enum reachable
+/// through more than one variant is an error on each of them, until all but one are marked
+/// `#[nestum(skip_from)]`. `TryFrom` needs to move the inner value out, so it is only generated
+/// through `Box` or no pointer at all.
+fn build_conversions(
+    root: &ItemEnum,
+    options: &EnumOptions,
+    module_path: &str,
+    resolver: &mut Resolver,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    if !options.conversions {
+        return Ok(quote! {});
     }
-}
+    let mut conversions = Vec::new();
+    let mut visited = vec![(module_path.to_string(), root.ident.to_string())];
+    collect_conversions(
+        root,
+        options,
+        module_path,
+        &GenericSubst::identity(),
+        &mut Vec::new(),
+        &mut visited,
+        &mut conversions,
+        resolver,
+    )?;
 
-fn rewrite_pat_path(
-    pat_path: PatPath,
-    current_file: &str,
-    module_root: &std::path::Path,
-    current_module: &str,
-    enums_by_ident: &HashMap<String, ItemEnum>,
-    cache: &mut HashMap<String, HashMap<String, ItemEnum>>,
-) -> Result<Pat, syn::Error> {
-    let Some((module_path, explicit_crate, outer_enum, outer_variant, inner_variant)) =
-        split_nested_path(&pat_path.path)?
-    else {
-        if let Some(new_path) = rewrite_outer_variant_path(
-            &pat_path.path,
-            current_file,
-            module_root,
-            current_module,
-            enums_by_ident,
-            cache,
-        )? {
-            return Ok(Pat::Path(PatPath {
-                attrs: pat_path.attrs,
-                qself: pat_path.qself,
-                path: new_path,
-            }));
-        }
-        return Ok(Pat::Path(pat_path));
+    let type_key = |conversion: &Conversion| {
+        let ty = &conversion.inner_ty;
+        quote!(#ty).to_string()
     };
+    let root_ident = &root.ident;
+    let (impl_generics, ty_generics, where_clause) = root.generics.split_for_impl();
+    let root_ty = quote! { #root_ident #ty_generics };
+    let mut impls = Vec::new();
+    for conversion in conversions.iter() {
+        let key = type_key(conversion);
+        if conversions
+            .iter()
+            .filter(|other| type_key(other) == key)
+            .count()
+            > 1
+        {
+            let inner = match &conversion.inner_ty {
+                syn::Type::Path(type_path) => type_path.path.segments.last().map(|s| &s.ident),
+                _ => None,
+            };
+            let message = format!(
+                "{} is nested by more than one variant of {root_ident}, so From and TryFrom \
+would be ambiguous; mark all but one of those variants with #[nestum(skip_from)]",
+                quote!(#inner)
+            );
+            impls.push(syn::Error::new(conversion.span, message).to_compile_error());
+            continue;
+        }
 
-    let Some(outer_info) = resolve_enum_from_path(
-        &module_path,
-        explicit_crate,
-        &outer_enum,
-        current_file,
-        module_root,
-        current_module,
-        enums_by_ident,
-        cache,
-    )?
-    else {
-        return Ok(Pat::Path(pat_path));
-    };
+        let inner_ty = &conversion.inner_ty;
+        let levels = &conversion.levels;
+        let wrapped = wrap_conversion(levels, quote! { value });
+        impls.push(quote! {
+            impl #impl_generics ::core::convert::From<#inner_ty> for #root_ty #where_clause {
+                fn from(value: #inner_ty) -> Self {
+                    #wrapped
+                }
+            }
+        });
 
-    let (outer_enum_item, outer_marked) = outer_info;
-    if !outer_marked {
-        return Err(syn::Error::new(
-            pat_path.span(),
-            format!(
-                "enum {} is not marked with #[nestum]; \
-only #[nestum] enums support nested match patterns",
-                outer_enum
-            ),
-        ));
+        let movable = levels
+            .iter()
+            .all(|level| level.pointer.as_ref().is_none_or(Pointer::is_box));
+        if movable {
+            let unwrapped = unwrap_conversion(levels, 0, quote! { value });
+            impls.push(quote! {
+                impl #impl_generics ::core::convert::TryFrom<#root_ty> for #inner_ty
+                #where_clause
+                {
+                    type Error = #root_ty;
+
+                    fn try_from(value: #root_ty) -> ::core::result::Result<Self, Self::Error> {
+                        #unwrapped
+                    }
+                }
+            });
+        }
     }
 
-    let Some((inner_enum_ident, inner_enum_path, inner_explicit_crate)) =
-        resolve_inner_enum_path(&outer_enum_item, &outer_variant, enums_by_ident)?
-    else {
-        return Ok(Pat::Path(pat_path));
-    };
+    Ok(quote! { #(#impls)* })
+}
 
-    let inner_enum_info = resolve_enum_from_path(
-        &inner_enum_path,
-        inner_explicit_crate,
-        &inner_enum_ident,
-        current_file,
-        module_root,
-        current_module,
-        enums_by_ident,
-        cache,
-    )?;
+#[allow(clippy::too_many_arguments)]
+fn collect_conversions(
+    item: &ItemEnum,
+    options: &EnumOptions,
+    module_path: &str,
+    subst: &GenericSubst,
+    levels: &mut Vec<ConversionLevel>,
+    visited: &mut Vec<(String, String)>,
+    conversions: &mut Vec<Conversion>,
+    resolver: &mut Resolver,
+) -> Result<(), syn::Error> {
+    for variant in item.variants.iter() {
+        if VariantOptions::parse(&variant.attrs)?.skip_from {
+            continue;
+        }
+        let Some(child) = resolver.nested_child(item, options, module_path, variant)? else {
+            continue;
+        };
+        if !context_fields(&child.fields, child.index).is_empty() {
+            continue;
+        }
+        // Converting an enum into itself is covered by the standard blanket impls.
+        let key = (child.module_path.clone(), child.enum_item.ident.to_string());
+        if visited.contains(&key) {
+            continue;
+        }
 
-    let (inner_enum_item, inner_marked) = inner_enum_info.ok_or_else(|| {
-        syn::Error::new(
-            pat_path.span(),
-            format!(
-                "inner enum {} not found for {}::{}; \
-ensure it is declared in the referenced module",
-                inner_enum_ident, outer_enum, outer_variant
-            ),
-        )
-    })?;
-    if !inner_marked {
-        return Err(syn::Error::new(
-            pat_path.span(),
-            format!(
-                "inner enum {} is not marked with #[nestum]; \
-only #[nestum] enums support nested match patterns",
-                inner_enum_ident
-            ),
-        ));
+        let variant_ident = &variant.ident;
+        let variant_path = if levels.is_empty() {
+            let ident = &item.ident;
+            quote! { #ident::#variant_ident }
+        } else {
+            let enum_path = enum_type_path(module_path, item);
+            quote! { #enum_path::#variant_ident }
+        };
+        let applied_ty = subst.apply(&child.field_ty);
+        levels.push(ConversionLevel {
+            variant: variant_path,
+            span: variant_ident.span(),
+            pointer: child.pointer.clone(),
+            fields: child.fields.clone(),
+            index: child.index,
+        });
+        conversions.push(Conversion {
+            inner_ty: inner_enum_type(&child, &applied_ty),
+            levels: levels.clone(),
+            span: levels[0].span,
+        });
+
+        let child_options = EnumOptions::from_attrs(&child.enum_item)?;
+        let child_subst = GenericSubst::new(&child.enum_item.generics, &applied_ty)?;
+        visited.push(key);
+        collect_conversions(
+            &child.enum_item,
+            &child_options,
+            &child.module_path,
+            &child_subst,
+            levels,
+            visited,
+            conversions,
+            resolver,
+        )?;
+        visited.pop();
+        levels.pop();
     }
+    Ok(())
+}
 
-    ensure_inner_variant_exists(&inner_enum_item, &inner_variant)?;
+/// `value` wrapped in every level's variant, allocating pointers on the way.
+fn wrap_conversion(
+    levels: &[ConversionLevel],
+    value: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    levels.iter().rev().fold(value, |expr, level| {
+        let expr = match &level.pointer {
+            Some(pointer) => pointer.wrap_expr(expr),
+            None => expr,
+        };
+        nested_variant_tokens(
+            level.variant.clone(),
+            &level.fields,
+            level.index,
+            Vec::new(),
+            expr,
+        )
+    })
+}
 
-    let outer_module_idents = effective_module_idents(&module_path, explicit_crate, current_module);
-    let inner_module_idents = if inner_enum_path.is_empty() {
-        outer_module_idents.clone()
+/// Matches `value` down through `levels[depth..]`, rebuilding the outer value on a mismatch.
+fn unwrap_conversion(
+    levels: &[ConversionLevel],
+    depth: usize,
+    value: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let level = &levels[depth];
+    let binding = format_ident!("v{depth}");
+    let pat = nested_variant_tokens(
+        level.variant.clone(),
+        &level.fields,
+        level.index,
+        Vec::new(),
+        quote! { #binding },
+    );
+    let inner = match &level.pointer {
+        Some(_) => quote! { *#binding },
+        None => quote! { #binding },
+    };
+    let matched = if depth + 1 == levels.len() {
+        quote! { ::core::result::Result::Ok(#inner) }
     } else {
-        effective_module_idents(&inner_enum_path, inner_explicit_crate, current_module)
+        unwrap_conversion(levels, depth + 1, inner)
     };
-    let outer_variant_path = build_path_from_idents(
-        outer_module_idents,
-        &[
-            outer_enum.clone(),
-            outer_enum.clone(),
-            outer_variant.clone(),
-        ],
-    );
-    let inner_variant_path = build_path_from_idents(
-        inner_module_idents,
-        &[
-            inner_enum_ident.clone(),
-            inner_enum_ident.clone(),
-            inner_variant,
-        ],
-    );
-
-    let inner_pat = Pat::Path(PatPath {
-        attrs: Vec::new(),
-        qself: None,
-        path: inner_variant_path,
-    });
-
-    Ok(Pat::TupleStruct(PatTupleStruct {
-        attrs: Vec::new(),
-        qself: None,
-        path: outer_variant_path,
-        paren_token: Default::default(),
-        elems: Punctuated::from_iter(std::iter::once(inner_pat)),
-    }))
+    let rebuilt = wrap_conversion(&levels[..depth], quote! { other });
+    quote! {
+        match #value {
+            #pat => #matched,
+            #[allow(unreachable_patterns)]
+            other => ::core::result::Result::Err(#rebuilt),
+        }
+    }
 }
 
-fn rewrite_pat_tuple_struct(
-    pat_tuple: PatTupleStruct,
-    current_file: &str,
-    module_root: &std::path::Path,
-    current_module: &str,
-    enums_by_ident: &HashMap<String, ItemEnum>,
-    cache: &mut HashMap<String, HashMap<String, ItemEnum>>,
-) -> Result<Pat, syn::Error> {
-    let Some((module_path, explicit_crate, outer_enum, outer_variant, inner_variant)) =
-        split_nested_path(&pat_tuple.path)?
-    else {
-        if let Some(new_path) = rewrite_outer_variant_path(
-            &pat_tuple.path,
-            current_file,
-            module_root,
-            current_module,
-            enums_by_ident,
-            cache,
-        )? {
-            return Ok(Pat::TupleStruct(PatTupleStruct {
-                attrs: pat_tuple.attrs,
-                qself: pat_tuple.qself,
-                path: new_path,
-                paren_token: pat_tuple.paren_token,
-                elems: pat_tuple.elems,
-            }));
-        }
-        return Ok(Pat::TupleStruct(pat_tuple));
+/// `#[nestum(accessors)]`: inherent accessors on the root enum: `is_x`, `as_x`, `as_x_mut` and
+/// `into_x` for each nested variant `X`, plus `is_x_y` predicates for every variant `Y` below it,
+/// at any depth. Mutable and owning accessors need the inner value in place or boxed, so `Rc` and
+/// `Arc` variants only get the shared ones. When two methods would share a name, the first one
+/// wins.
+fn build_accessors(
+    root: &ItemEnum,
+    options: &EnumOptions,
+    module_path: &str,
+    resolver: &mut Resolver,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    if !options.accessors {
+        return Ok(quote! {});
+    }
+    let mut methods = Vec::new();
+    let mut predicates = Vec::new();
+    let mut visited = vec![(module_path.to_string(), root.ident.to_string())];
+    for variant in root.variants.iter() {
+        let Some(child) = resolver.nested_child(root, options, module_path, variant)? else {
+            continue;
+        };
+        let variant_ident = &variant.ident;
+        let name = to_snake_case(&variant_ident.to_string());
+        let inner_ty = inner_enum_type(&child, &child.field_ty);
+        let pat = nested_variant_tokens(
+            quote! { Self::#variant_ident },
+            &child.fields,
+            child.index,
+            Vec::new(),
+            quote! { inner },
+        );
+        let owned = child.pointer.as_ref().is_none_or(Pointer::is_box);
+        let (shared, unique, moved) = match &child.pointer {
+            Some(_) => (
+                quote! { &**inner },
+                quote! { &mut **inner },
+                quote! { *inner },
+            ),
+            None => (quote! { inner }, quote! { inner }, quote! { inner }),
+        };
+
+        let is_fn = format_ident!("is_{}", name);
+        let as_fn = format_ident!("as_{}", name);
+        let is_doc = format!("Returns `true` if this is a `{variant_ident}` value.");
+        let as_doc = format!("The inner enum of a `{variant_ident}` value.");
+        methods.push((
+            is_fn.to_string(),
+            quote! {
+                #[doc = #is_doc]
+                pub fn #is_fn(&self) -> bool {
+                    ::core::matches!(self, Self::#variant_ident { .. })
+                }
+            },
+        ));
+        methods.push((
+            as_fn.to_string(),
+            quote! {
+                #[doc = #as_doc]
+                pub fn #as_fn(&self) -> ::core::option::Option<&#inner_ty> {
+                    match self {
+                        #pat => ::core::option::Option::Some(#shared),
+                        #[allow(unreachable_patterns)]
+                        _ => ::core::option::Option::None,
+                    }
+                }
+            },
+        ));
+        if owned {
+            let as_mut_fn = format_ident!("as_{}_mut", name);
+            let into_fn = format_ident!("into_{}", name);
+            let as_mut_doc = format!("The inner enum of a `{variant_ident}` value, mutably.");
+            let into_doc = format!(
+                "The inner enum of a `{variant_ident}` value, or `self` for any other variant."
+            );
+            methods.push((
+                as_mut_fn.to_string(),
+                quote! {
+                    #[doc = #as_mut_doc]
+                    pub fn #as_mut_fn(&mut self) -> ::core::option::Option<&mut #inner_ty> {
+                        match self {
+                            #pat => ::core::option::Option::Some(#unique),
+                            #[allow(unreachable_patterns)]
+                            _ => ::core::option::Option::None,
+                        }
+                    }
+                },
+            ));
+            methods.push((
+                into_fn.to_string(),
+                quote! {
+                    #[doc = #into_doc]
+                    pub fn #into_fn(self) -> ::core::result::Result<#inner_ty, Self> {
+                        match self {
+                            #pat => ::core::result::Result::Ok(#moved),
+                            #[allow(unreachable_patterns)]
+                            other => ::core::result::Result::Err(other),
+                        }
+                    }
+                },
+            ));
+        }
+
+        let key = (child.module_path.clone(), child.enum_item.ident.to_string());
+        if visited.contains(&key) {
+            continue;
+        }
+        let mut levels = vec![PredicateLevel {
+            path: quote! { Self::#variant_ident },
+            child,
+        }];
+        visited.push(key);
+        collect_predicates(&name, &mut levels, &mut visited, &mut predicates, resolver)?;
+        visited.pop();
+    }
+    methods.extend(predicates);
+
+    let mut seen = HashSet::new();
+    let methods = methods
+        .into_iter()
+        .filter(|(name, _)| seen.insert(name.clone()))
+        .map(|(_, method)| method);
+    let ident = &root.ident;
+    let (impl_generics, ty_generics, where_clause) = root.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #(#methods)*
+        }
+    })
+}
+
+/// A nested variant on the way down to the variants a predicate checks for.
+struct PredicateLevel {
+    path: proc_macro2::TokenStream,
+    child: NestedChild,
+}
+
+/// `is_<path>_<variant>` predicates for each variant of the innermost enum in `levels`,
+/// recursing into its own nested variants.
+fn collect_predicates(
+    name: &str,
+    levels: &mut Vec<PredicateLevel>,
+    visited: &mut Vec<(String, String)>,
+    predicates: &mut Vec<(String, proc_macro2::TokenStream)>,
+    resolver: &mut Resolver,
+) -> Result<(), syn::Error> {
+    let current = &levels[levels.len() - 1].child;
+    let item = current.enum_item.clone();
+    let module_path = current.module_path.clone();
+    let enum_path = current.enum_path();
+    let options = EnumOptions::from_attrs(&item)?;
+    for variant in item.variants.iter() {
+        let variant_ident = &variant.ident;
+        let name = format!("{name}_{}", to_snake_case(&variant_ident.to_string()));
+        let check = predicate_check(
+            levels,
+            0,
+            quote! { self },
+            &quote! { #enum_path::#variant_ident { .. } },
+        );
+        let method = format_ident!("is_{}", name);
+        let doc = format!("Returns `true` if this value nests a `{variant_ident}` value.");
+        predicates.push((
+            method.to_string(),
+            quote! {
+                #[doc = #doc]
+                pub fn #method(&self) -> bool {
+                    #check
+                }
+            },
+        ));
+
+        let Some(child) = resolver.nested_child(&item, &options, &module_path, variant)? else {
+            continue;
+        };
+        let key = (child.module_path.clone(), child.enum_item.ident.to_string());
+        if visited.contains(&key) {
+            continue;
+        }
+        levels.push(PredicateLevel {
+            path: quote! { #enum_path::#variant_ident },
+            child,
+        });
+        visited.push(key);
+        collect_predicates(&name, levels, visited, predicates, resolver)?;
+        visited.pop();
+        levels.pop();
+    }
+    Ok(())
+}
+
+/// Matches `value` down through `levels[depth..]`, then checks the innermost value against
+/// `leaf`, dereferencing pointers on the way.
+fn predicate_check(
+    levels: &[PredicateLevel],
+    depth: usize,
+    value: proc_macro2::TokenStream,
+    leaf: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let Some(level) = levels.get(depth) else {
+        return quote! { ::core::matches!(#value, #leaf) };
+    };
+    let binding = format_ident!("v{depth}");
+    let pat = nested_variant_tokens(
+        level.path.clone(),
+        &level.child.fields,
+        level.child.index,
+        Vec::new(),
+        quote! { #binding },
+    );
+    let inner = match &level.child.pointer {
+        Some(_) => quote! { &**#binding },
+        None => quote! { #binding },
+    };
+    let check = predicate_check(levels, depth + 1, inner, leaf);
+    quote! {
+        match #value {
+            #pat => #check,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+/// A variant anywhere in the tree below a root enum.
+#[derive(Clone)]
+struct TreeNode {
+    /// Variant names from the root enum down to this variant.
+    path: Vec<syn::Ident>,
+    /// The enum declaring the variant, as used in patterns (`Self` for the root).
+    enum_path: proc_macro2::TokenStream,
+    variant: syn::Variant,
+    /// Types of the variant's fields in terms of the root enum's generic parameters.
+    field_tys: Vec<syn::Type>,
+    /// Index of the nested variant this one sits below.
+    parent: Option<usize>,
+    /// The enum this variant nests, unless it is a leaf or closes a cycle.
+    child: Option<NestedChild>,
+}
+
+/// Every variant reachable from a root enum, each nested variant followed by its subtree.
+struct Tree {
+    nodes: Vec<TreeNode>,
+    visited: Vec<(String, String)>,
+}
+
+impl Tree {
+    fn collect(
+        root: &ItemEnum,
+        options: &EnumOptions,
+        module_path: &str,
+        resolver: &mut Resolver,
+    ) -> Result<Self, syn::Error> {
+        let mut tree = Tree {
+            nodes: Vec::new(),
+            visited: vec![(module_path.to_string(), root.ident.to_string())],
+        };
+        tree.collect_level(
+            root,
+            options,
+            module_path,
+            quote! { Self },
+            &GenericSubst::identity(),
+            None,
+            resolver,
+        )?;
+        Ok(tree)
+    }
+
+    /// `subst` maps the generic parameters of `item` onto the root's.
+    #[allow(clippy::too_many_arguments)]
+    fn collect_level(
+        &mut self,
+        item: &ItemEnum,
+        options: &EnumOptions,
+        module_path: &str,
+        enum_path: proc_macro2::TokenStream,
+        subst: &GenericSubst,
+        parent: Option<usize>,
+        resolver: &mut Resolver,
+    ) -> Result<(), syn::Error> {
+        for variant in item.variants.iter() {
+            let mut path = parent.map_or_else(Vec::new, |parent| self.nodes[parent].path.clone());
+            path.push(variant.ident.clone());
+            let index = self.nodes.len();
+            self.nodes.push(TreeNode {
+                path,
+                enum_path: enum_path.clone(),
+                variant: variant.clone(),
+                field_tys: variant
+                    .fields
+                    .iter()
+                    .map(|field| subst.apply(&field.ty))
+                    .collect(),
+                parent,
+                child: None,
+            });
+
+            let Some(child) = resolver.nested_child(item, options, module_path, variant)? else {
+                continue;
+            };
+            // Nested fields hold the shadowed enum type, not the module the written name resolves to.
+            let inner = inner_enum_type(&child, &subst.apply(&child.field_ty));
+            let field_ty = &mut self.nodes[index].field_tys[child.index];
+            *field_ty = match &child.pointer {
+                Some(_) => Pointer::rewrap_type(field_ty, inner),
+                None => inner,
+            };
+            let key = (child.module_path.clone(), child.enum_item.ident.to_string());
+            if self.visited.contains(&key) {
+                continue;
+            }
+            let child_item = child.enum_item.clone();
+            let child_options = EnumOptions::from_attrs(&child_item)?;
+            let child_module = child.module_path.clone();
+            let child_path = child.enum_path();
+            let child_subst =
+                GenericSubst::new(&child_item.generics, &subst.apply(&child.field_ty))?;
+            self.nodes[index].child = Some(child);
+            self.visited.push(key);
+            self.collect_level(
+                &child_item,
+                &child_options,
+                &child_module,
+                quote! { #child_path },
+                &child_subst,
+                Some(index),
+                resolver,
+            )?;
+            self.visited.pop();
+        }
+        Ok(())
+    }
+
+    /// Indices of the variants that nest nothing further, in declaration order.
+    fn leaves(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(|&index| self.nodes[index].child.is_none())
+    }
+
+    /// The context fields of the nested variant `node`, `depth` levels below the root, with
+    /// the names `leaf_match` binds them to and their types.
+    fn level_context(node: &TreeNode, depth: usize) -> Vec<(syn::Ident, syn::Type)> {
+        let Some(child) = &node.child else {
+            return Vec::new();
+        };
+        node.field_tys
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != child.index)
+            .map(|(i, ty)| (format_ident!("__nestum_ctx{}_{}", depth, i), ty.clone()))
+            .collect()
+    }
+
+    /// The context fields above the leaf at `index`, root first, with the names `leaf_match`
+    /// binds them to, their types and their names in the variants that declare them.
+    fn context_bindings(&self, index: usize) -> Vec<(syn::Ident, syn::Type, Option<syn::Ident>)> {
+        let mut ancestors = Vec::new();
+        let mut parent = self.nodes[index].parent;
+        while let Some(index) = parent {
+            ancestors.push(index);
+            parent = self.nodes[index].parent;
+        }
+        ancestors
+            .into_iter()
+            .rev()
+            .enumerate()
+            .flat_map(|(depth, index)| {
+                let node = &self.nodes[index];
+                let names = node.variant.fields.iter().enumerate();
+                let child_index = node.child.as_ref().map_or(usize::MAX, |child| child.index);
+                Self::level_context(node, depth)
+                    .into_iter()
+                    .zip(names.filter(move |(i, _)| *i != child_index))
+                    .map(|((binding, ty), (_, field))| (binding, ty, field.ident.clone()))
+            })
+            .collect()
+    }
+
+    /// The tree with every nested variant behind a pointer other than `Box` turned into a leaf,
+    /// for code that needs to mutate or move the values inside.
+    fn owned(&self) -> Tree {
+        let mut nodes: Vec<TreeNode> = Vec::new();
+        let mut kept: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate() {
+            let parent = match node.parent {
+                Some(parent) => match kept[parent] {
+                    Some(parent) if nodes[parent].child.is_some() => Some(parent),
+                    _ => continue,
+                },
+                None => None,
+            };
+            let mut node = node.clone();
+            node.parent = parent;
+            if node.child.as_ref().is_some_and(|child| {
+                child
+                    .pointer
+                    .as_ref()
+                    .is_some_and(|pointer| !pointer.is_box())
+            }) {
+                node.child = None;
+            }
+            kept[index] = Some(nodes.len());
+            nodes.push(node);
+        }
+        Tree {
+            nodes,
+            visited: Vec::new(),
+        }
+    }
+
+    /// The members of the leaf at `index` with the names `leaf_match` binds them to.
+    fn leaf_bindings(&self, index: usize) -> Vec<(syn::Member, syn::Ident)> {
+        self.nodes[index]
+            .variant
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let member = match &field.ident {
+                    Some(ident) => syn::Member::Named(ident.clone()),
+                    None => syn::Member::Unnamed(syn::Index::from(i)),
+                };
+                (member, format_ident!("__nestum_{}", i))
+            })
+            .collect()
+    }
+
+    /// An expression building the leaf at `index` from `values` for the context fields above it
+    /// (root first) and then its own fields, wrapped in the variants above it up to the root
+    /// enum, which is spelled `root` instead of `Self`.
+    fn construct(
+        &self,
+        index: usize,
+        values: &[proc_macro2::TokenStream],
+        root: &proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let path_of = |node: &TreeNode| match node.parent {
+            Some(_) => node.enum_path.clone(),
+            None => root.clone(),
+        };
+        let node = &self.nodes[index];
+        let (context, values) = values.split_at(values.len() - node.variant.fields.len());
+        let mut context = context.to_vec();
+        let enum_path = &path_of(node);
+        let ident = &node.variant.ident;
+        let mut expr = match node.variant.fields {
+            Fields::Unit => quote! { #enum_path::#ident },
+            _ => {
+                let members = self
+                    .leaf_bindings(index)
+                    .into_iter()
+                    .map(|(member, _)| member);
+                quote! { #enum_path::#ident { #(#members: #values),* } }
+            }
+        };
+        let mut parent = node.parent;
+        while let Some(index) = parent {
+            let node = &self.nodes[index];
+            let child = node
+                .child
+                .as_ref()
+                .expect("nodes with children nest an enum");
+            let inner = match &child.pointer {
+                Some(pointer) => pointer.wrap_expr(expr),
+                None => expr,
+            };
+            let enum_path = &path_of(node);
+            let ident = &node.variant.ident;
+            let level = context.split_off(context.len() - (child.fields.len() - 1));
+            expr = nested_variant_tokens(
+                quote! { #enum_path::#ident },
+                &child.fields,
+                child.index,
+                level,
+                inner,
+            );
+            parent = node.parent;
+        }
+        expr
+    }
+
+    /// The path of every variant spelled with the enum's `rename_all` and `separator`, failing
+    /// if two leaves end up with the same spelling.
+    fn spellings(
+        &self,
+        options: &EnumOptions,
+        root_ident: &syn::Ident,
+    ) -> Result<Vec<String>, syn::Error> {
+        let separator = options.separator.as_deref().unwrap_or("::");
+        let names: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                node.path
+                    .iter()
+                    .map(|ident| options.rename_all.apply(&ident.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(separator)
+            })
+            .collect();
+        let mut seen = HashMap::new();
+        for index in self.leaves() {
+            if let Some(other) = seen.insert(names[index].as_str(), index) {
+                return Err(syn::Error::new(
+                    root_ident.span(),
+                    format!(
+                        "{} and {} are both spelled \"{}\"; choose another rename_all or separator",
+                        join_path(&self.nodes[other].path),
+                        join_path(&self.nodes[index].path),
+                        names[index]
+                    ),
+                ));
+            }
+        }
+        Ok(names)
+    }
+
+    /// The first variant that keeps the tree from being built from its path alone: a leaf with
+    /// fields or a nested variant with context fields.
+    fn first_non_unit(&self) -> Option<&TreeNode> {
+        self.nodes.iter().find(|node| match &node.child {
+            Some(child) => child.fields.len() > 1,
+            None => !matches!(node.variant.fields, Fields::Unit),
+        })
+    }
+
+    /// A `match` on `value`, the enum below `parent` (the root for `None`), with one arm per
+    /// leaf whose body `arm` builds from the leaf's index. Unless `access` is `LeafAccess::None`,
+    /// each arm binds the leaf's fields and the context fields above it as named by
+    /// `leaf_bindings` and `context_bindings`.
+    fn leaf_match(
+        &self,
+        value: proc_macro2::TokenStream,
+        parent: Option<usize>,
+        access: LeafAccess,
+        arm: &dyn Fn(usize) -> proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let depth = parent.map_or(0, |parent| self.nodes[parent].path.len());
+        let binding = format_ident!("v{depth}");
+        let mut arms = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.parent != parent {
+                continue;
+            }
+            let enum_path = &node.enum_path;
+            let ident = &node.variant.ident;
+            match &node.child {
+                Some(child) => {
+                    let context = match access {
+                        LeafAccess::None => Vec::new(),
+                        _ => Self::level_context(node, depth)
+                            .into_iter()
+                            .map(|(binding, _)| quote! { #binding })
+                            .collect(),
+                    };
+                    let pat = nested_variant_tokens(
+                        quote! { #enum_path::#ident },
+                        &child.fields,
+                        child.index,
+                        context,
+                        quote! { #binding },
+                    );
+                    let inner = match (&child.pointer, access) {
+                        (None, _) => quote! { #binding },
+                        (Some(_), LeafAccess::None | LeafAccess::Ref) => quote! { &**#binding },
+                        (Some(_), LeafAccess::Mut) => quote! { &mut **#binding },
+                        (Some(_), LeafAccess::Owned) => quote! { *#binding },
+                    };
+                    let body = self.leaf_match(inner, Some(index), access, arm);
+                    arms.push(quote! { #pat => #body, });
+                }
+                None => {
+                    let body = arm(index);
+                    let fields = if access == LeafAccess::None {
+                        quote! { .. }
+                    } else {
+                        let (members, bindings): (Vec<_>, Vec<_>) =
+                            self.leaf_bindings(index).into_iter().unzip();
+                        quote! { #(#members: #bindings),* }
+                    };
+                    arms.push(quote! { #enum_path::#ident { #fields } => #body, });
+                }
+            }
+        }
+        if arms.is_empty() {
+            // An empty match needs the place itself; only owned values are not behind a reference.
+            return match access {
+                LeafAccess::Owned => quote! { match #value {} },
+                _ => quote! { match *#value {} },
+            };
+        }
+        quote! {
+            match #value {
+                #(#arms)*
+            }
+        }
+    }
+}
+
+/// How `Tree::leaf_match` reaches the fields of each leaf.
+#[derive(Clone, Copy, PartialEq)]
+enum LeafAccess {
+    /// Only tells the leaves apart, binding nothing.
+    None,
+    /// Binds shared references.
+    Ref,
+    /// Binds mutable references; pointers on the way must be `Box`.
+    Mut,
+    /// Moves the fields out; pointers on the way must be `Box`.
+    Owned,
+}
+
+/// `#[nestum(kind)]`: the flattened kind enum of a root enum (one fieldless variant per variant
+/// in the tree) and the root's `kind()` method.
+fn build_kind(
+    root: &ItemEnum,
+    options: &EnumOptions,
+    tree: &Tree,
+) -> Result<(proc_macro2::TokenStream, proc_macro2::TokenStream), syn::Error> {
+    // Selectors and sets are spelled with kinds, so they bring the kind enum along.
+    if options.kind.is_none() && !options.selector && !options.set {
+        return Ok((quote! {}, quote! {}));
+    }
+    let vis = &root.vis;
+    let root_ident = &root.ident;
+    let kind_ident = kind_ident(root_ident, options);
+
+    let mut names = HashMap::new();
+    let mut kinds = Vec::new();
+    for node in tree.nodes.iter() {
+        let name = kind_variant(node).to_string();
+        let path = join_path(&node.path);
+        if let Some(other) = names.insert(name.clone(), path.clone()) {
+            return Err(syn::Error::new(
+                node.variant.ident.span(),
+                format!(
+                    "nested variants {other} and {path} both map to {kind_ident}::{name}; \
+kind variants join the variant names of their path, so rename one of the variants"
+                ),
+            ));
+        }
+        kinds.push(kind_variant(node));
+    }
+
+    let variant_docs = tree.nodes.iter().map(|node| {
+        let doc = format!("`{}`", join_path(&node.path));
+        quote! { #[doc = #doc] }
+    });
+    let parents = tree.nodes.iter().map(|node| match node.parent {
+        Some(parent) => {
+            let parent = &kinds[parent];
+            quote! { ::core::option::Option::Some(Self::#parent) }
+        }
+        None => quote! { ::core::option::Option::None },
+    });
+    let leaves = tree.nodes.iter().map(|node| node.child.is_none());
+    let count = kinds.len();
+    let enum_doc = format!("Kinds of `{root_ident}` values: one per variant, at any depth.");
+    let parent_match = if kinds.is_empty() {
+        quote! { match self {} }
+    } else {
+        quote! { match self { #(Self::#kinds => #parents,)* } }
+    };
+    let leaf_match = if kinds.is_empty() {
+        quote! { match self {} }
+    } else {
+        quote! { match self { #(Self::#kinds => #leaves,)* } }
+    };
+    let kind_enum = quote! {
+        #[doc = #enum_doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        #vis enum #kind_ident {
+            #(#variant_docs #kinds,)*
+        }
+
+        impl #kind_ident {
+            /// Every kind, each nested variant followed by the kinds below it.
+            pub const ALL: [Self; #count] = [#(Self::#kinds),*];
+
+            /// The kind of the nested variant this one sits below.
+            pub const fn parent(self) -> ::core::option::Option<Self> {
+                #parent_match
+            }
+
+            /// Returns `true` for kinds that `kind()` can return, i.e. variants that nest nothing.
+            pub const fn is_leaf(self) -> bool {
+                #leaf_match
+            }
+        }
+    };
+
+    let body = tree.leaf_match(quote! { self }, None, LeafAccess::None, &|index| {
+        let kind = &kinds[index];
+        quote! { #kind_ident::#kind }
+    });
+    let (impl_generics, ty_generics, where_clause) = root.generics.split_for_impl();
+    let kind_impl = quote! {
+        impl #impl_generics #root_ident #ty_generics #where_clause {
+            /// The kind of the innermost variant of this value.
+            pub fn kind(&self) -> #kind_ident {
+                #body
+            }
+        }
+    };
+    Ok((kind_enum, kind_impl))
+}
+
+/// The selector enum of a root enum, naming all values, the values of one leaf kind or the
+/// values below one nested variant, with its `Display` and `FromStr` impls.
+fn build_selector(
+    root: &ItemEnum,
+    options: &EnumOptions,
+    tree: &Tree,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    if !options.selector {
+        return Ok(quote! {});
+    }
+    let vis = &root.vis;
+    let root_ident = &root.ident;
+    let (impl_generics, ty_generics, where_clause) = root.generics.split_for_impl();
+    let root_path = match &options.companion {
+        Some(_) => quote! { #root_ident },
+        None => quote! { #root_ident::#root_ident },
+    };
+    let kind_ident = kind_ident(root_ident, options);
+    let selector_ident = format_ident!("{}Selector", root_ident);
+    let error_ident = format_ident!("Parse{}Error", selector_ident);
+    let separator = options.separator.as_deref().unwrap_or("::");
+    let names = tree.spellings(options, root_ident)?;
+
+    let mut strings = vec!["*".to_string()];
+    let mut values = vec![quote! { Self::All }];
+    for (index, node) in tree.nodes.iter().enumerate() {
+        let kind = kind_variant(node);
+        match node.child {
+            Some(_) => {
+                strings.push(format!("{}{separator}*", names[index]));
+                values.push(quote! { Self::Subtree(#kind_ident::#kind) });
+            }
+            None => {
+                strings.push(names[index].clone());
+                values.push(quote! { Self::Kind(#kind_ident::#kind) });
+            }
+        }
+    }
+    let kinds: Vec<syn::Ident> = tree.nodes.iter().map(kind_variant).collect();
+    let kind_names = kinds.iter().zip(&names).map(|(kind, name)| {
+        quote! { #kind_ident::#kind => #name, }
+    });
+    let name_match = if kinds.is_empty() {
+        quote! { match kind {} }
+    } else {
+        quote! { match kind { #(#kind_names)* } }
+    };
+
+    let selector_doc = format!(
+        "Selects `{root_ident}` values: all of them, those of one leaf kind, or those below one \
+nested variant. Parses from and displays as `*`, `Leaf` or `Nested{separator}*`, spelled like \
+the variant paths."
+    );
+    let error_doc = format!("The error returned when parsing a `{selector_ident}` fails.");
+    Ok(quote! {
+        #[doc = #selector_doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #vis enum #selector_ident {
+            /// Every value.
+            All,
+            /// Values of exactly this kind.
+            Kind(#kind_ident),
+            /// Values of this kind or of any kind below it.
+            Subtree(#kind_ident),
+        }
+
+        impl #selector_ident {
+            /// Returns `true` if the selector covers values of `kind`.
+            pub fn matches_kind(&self, kind: #kind_ident) -> bool {
+                match *self {
+                    Self::All => true,
+                    Self::Kind(selected) => kind == selected,
+                    Self::Subtree(selected) => {
+                        let mut kind = ::core::option::Option::Some(kind);
+                        while let ::core::option::Option::Some(current) = kind {
+                            if current == selected {
+                                return true;
+                            }
+                            kind = current.parent();
+                        }
+                        false
+                    }
+                }
+            }
+
+            /// Returns `true` if the selector covers `value`.
+            pub fn matches #impl_generics (&self, value: &#root_path #ty_generics) -> bool
+            #where_clause
+            {
+                self.matches_kind(value.kind())
+            }
+        }
+
+        impl ::core::fmt::Display for #selector_ident {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                let name = |kind: #kind_ident| -> &'static str { #name_match };
+                match *self {
+                    Self::All => f.write_str("*"),
+                    Self::Kind(kind) => f.write_str(name(kind)),
+                    Self::Subtree(kind) => ::core::write!(f, "{}{}*", name(kind), #separator),
+                }
+            }
+        }
+
+        impl ::core::str::FromStr for #selector_ident {
+            type Err = #error_ident;
+
+            fn from_str(input: &str) -> ::core::result::Result<Self, Self::Err> {
+                match input {
+                    #(#strings => ::core::result::Result::Ok(#values),)*
+                    _ => ::core::result::Result::Err(#error_ident {
+                        input: ::std::string::String::from(input),
+                    }),
+                }
+            }
+        }
+
+        #[doc = #error_doc]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        #vis struct #error_ident {
+            input: ::std::string::String,
+        }
+
+        impl #error_ident {
+            /// The string that failed to parse.
+            pub fn input(&self) -> &str {
+                &self.input
+            }
+
+            /// Every string that parses, in declaration order.
+            pub fn expected(&self) -> &'static [&'static str] {
+                &[#(#strings),*]
+            }
+        }
+
+        impl ::core::fmt::Display for #error_ident {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                ::core::write!(
+                    f,
+                    "unknown {} \"{}\"; expected one of: {}",
+                    ::core::stringify!(#selector_ident),
+                    self.input,
+                    self.expected().join(", ")
+                )
+            }
+        }
+
+        impl ::std::error::Error for #error_ident {}
+    })
+}
+
+/// A bitset of the leaves of a root enum whose leaves are all unit variants, with set algebra
+/// and subtree insertion through the kind enum.
+fn build_set(
+    root: &ItemEnum,
+    options: &EnumOptions,
+    tree: &Tree,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    if !options.set {
+        return Ok(quote! {});
+    }
+    let root_ident = &root.ident;
+    if let Some(node) = tree.first_non_unit() {
+        return Err(syn::Error::new(
+            root_ident.span(),
+            format!(
+                "#[nestum(set)] requires a tree of unit variants, but {} has fields",
+                join_path(&node.path)
+            ),
+        ));
+    }
+    if !root.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            root.generics.span(),
+            "#[nestum(set)] does not support generic enums; remove the generic parameters",
+        ));
+    }
+    let vis = &root.vis;
+    let root_path = match &options.companion {
+        Some(_) => quote! { #root_ident },
+        None => quote! { #root_ident::#root_ident },
+    };
+    let kind_ident = kind_ident(root_ident, options);
+    let set_ident = format_ident!("{}Set", root_ident);
+    let iter_ident = format_ident!("{}Iter", set_ident);
+
+    let leaves: Vec<usize> = tree.leaves().collect();
+    let len = leaves.len();
+    let words = len.div_ceil(64);
+    let mut bits = vec![None; tree.nodes.len()];
+    for (bit, &index) in leaves.iter().enumerate() {
+        bits[index] = Some(bit);
+    }
+    // Leaves come right after the nested variant above them, so each subtree is a bit range.
+    let mut ranges: Vec<(usize, usize)> = vec![(usize::MAX, 0); tree.nodes.len()];
+    for (bit, &index) in leaves.iter().enumerate() {
+        let mut node = Some(index);
+        while let Some(index) = node {
+            let (start, end) = &mut ranges[index];
+            *start = (*start).min(bit);
+            *end = bit + 1;
+            node = tree.nodes[index].parent;
+        }
+    }
+    let mask = |(start, end): (usize, usize)| {
+        let words = (0..words).map(|word| {
+            let mut value = 0u64;
+            for bit in start.max(word * 64)..end.min(word * 64 + 64) {
+                value |= 1 << (bit - word * 64);
+            }
+            proc_macro2::Literal::u64_suffixed(value)
+        });
+        quote! { Self { bits: [#(#words),*] } }
+    };
+    let full = mask((0, len));
+    let subtree_arms = tree.nodes.iter().zip(&ranges).map(|(node, &range)| {
+        let kind = kind_variant(node);
+        let mask = mask(range);
+        quote! { #kind_ident::#kind => #mask, }
+    });
+    let subtree_match = if tree.nodes.is_empty() {
+        quote! { match kind {} }
+    } else {
+        quote! { match kind { #(#subtree_arms)* } }
+    };
+    // `kind()` only returns leaf kinds.
+    let bit_arms = tree.nodes.iter().zip(&bits).map(|(node, bit)| {
+        let kind = kind_variant(node);
+        match bit {
+            Some(bit) => quote! { #kind_ident::#kind => #bit, },
+            None => quote! { #kind_ident::#kind => ::core::unreachable!(), },
+        }
+    });
+    let bit_body = if tree.nodes.is_empty() {
+        quote! { match value.kind() {} }
+    } else {
+        quote! { match value.kind() { #(#bit_arms)* } }
+    };
+    let leaf_values = leaves
+        .iter()
+        .map(|&index| tree.construct(index, &[], &root_path));
+    let leaf_kinds = leaves.iter().map(|&index| {
+        let kind = kind_variant(&tree.nodes[index]);
+        quote! { #kind_ident::#kind }
+    });
+    let bit_numbers = 0..len;
+    let word_op = |op: proc_macro2::TokenStream| {
+        quote! {
+            let mut bits = self.bits;
+            let mut word = 0;
+            while word < #words {
+                bits[word] = #op;
+                word += 1;
+            }
+            Self { bits }
+        }
+    };
+    let union = word_op(quote! { bits[word] | other.bits[word] });
+    let intersection = word_op(quote! { bits[word] & other.bits[word] });
+    let difference = word_op(quote! { bits[word] & !other.bits[word] });
+    let symmetric_difference = word_op(quote! { bits[word] ^ other.bits[word] });
+    let complement = word_op(quote! { !bits[word] & Self::FULL.bits[word] });
+
+    let set_doc = format!(
+        "A set of `{root_ident}` values stored as one bit per leaf variant. Inserting a nested \
+`{kind_ident}` inserts every leaf below it."
+    );
+    let iter_doc = format!("An iterator over the values of a `{set_ident}`, in declaration order.");
+    let operators = [
+        ("BitOr", "bitor", "union"),
+        ("BitAnd", "bitand", "intersection"),
+        ("BitXor", "bitxor", "symmetric_difference"),
+        ("Sub", "sub", "difference"),
+    ]
+    .map(|(op, method, set_method)| {
+        let assign_op = format_ident!("{op}Assign");
+        let assign_method = format_ident!("{method}_assign");
+        let op = format_ident!("{op}");
+        let method = format_ident!("{method}");
+        let set_method = format_ident!("{set_method}");
+        quote! {
+            impl ::core::ops::#op for #set_ident {
+                type Output = Self;
+
+                fn #method(self, other: Self) -> Self {
+                    self.#set_method(other)
+                }
+            }
+
+            impl ::core::ops::#assign_op for #set_ident {
+                fn #assign_method(&mut self, other: Self) {
+                    *self = self.#set_method(other);
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        #[doc = #set_doc]
+        #[derive(Clone, Copy, PartialEq, Eq, Hash)]
+        #vis struct #set_ident {
+            bits: [u64; #words],
+        }
+
+        impl #set_ident {
+            /// The set of no values.
+            pub const EMPTY: Self = Self { bits: [0; #words] };
+
+            /// The set of every value.
+            pub const FULL: Self = #full;
+
+            const KINDS: [#kind_ident; #len] = [#(#leaf_kinds),*];
+
+            /// An empty set.
+            pub const fn new() -> Self {
+                Self::EMPTY
+            }
+
+            /// The set of the values of `kinds`, each leaf or nested kind contributing every leaf
+            /// at or below it.
+            pub const fn from_kinds<const N: usize>(kinds: [#kind_ident; N]) -> Self {
+                let mut set = Self::EMPTY;
+                let mut index = 0;
+                while index < N {
+                    set = set.with(kinds[index]);
+                    index += 1;
+                }
+                set
+            }
+
+            /// This set plus every leaf at or below `kind`.
+            pub const fn with(self, kind: #kind_ident) -> Self {
+                self.union(Self::subtree(kind))
+            }
+
+            /// This set minus every leaf at or below `kind`.
+            pub const fn without(self, kind: #kind_ident) -> Self {
+                self.difference(Self::subtree(kind))
+            }
+
+            const fn subtree(kind: #kind_ident) -> Self {
+                #subtree_match
+            }
+
+            fn bit(value: &#root_path) -> usize {
+                #bit_body
+            }
+
+            fn value(bit: usize) -> #root_path {
+                match bit {
+                    #(#bit_numbers => #leaf_values,)*
+                    _ => ::core::unreachable!(),
+                }
+            }
+
+            /// Adds `value`, returning `true` if it was not in the set.
+            pub fn insert(&mut self, value: #root_path) -> bool {
+                let bit = Self::bit(&value);
+                let mask = 1u64 << (bit % 64);
+                let added = self.bits[bit / 64] & mask == 0;
+                self.bits[bit / 64] |= mask;
+                added
+            }
+
+            /// Removes `value`, returning `true` if it was in the set.
+            pub fn remove(&mut self, value: &#root_path) -> bool {
+                let bit = Self::bit(value);
+                let mask = 1u64 << (bit % 64);
+                let removed = self.bits[bit / 64] & mask != 0;
+                self.bits[bit / 64] &= !mask;
+                removed
+            }
+
+            /// Returns `true` if the set contains `value`.
+            pub fn contains(&self, value: &#root_path) -> bool {
+                let bit = Self::bit(value);
+                self.bits[bit / 64] & (1u64 << (bit % 64)) != 0
+            }
+
+            /// Adds every leaf at or below `kind`, e.g. all of `Documents::*`.
+            pub fn insert_kind(&mut self, kind: #kind_ident) {
+                *self = self.with(kind);
+            }
+
+            /// Removes every leaf at or below `kind`.
+            pub fn remove_kind(&mut self, kind: #kind_ident) {
+                *self = self.without(kind);
+            }
+
+            /// Returns `true` if the set contains every leaf at or below `kind`.
+            pub const fn contains_kind(&self, kind: #kind_ident) -> bool {
+                Self::subtree(kind).is_subset(self)
+            }
+
+            /// Removes every value.
+            pub fn clear(&mut self) {
+                *self = Self::EMPTY;
+            }
+
+            /// The number of values in the set.
+            pub const fn len(&self) -> usize {
+                let mut len = 0;
+                let mut word = 0;
+                while word < #words {
+                    len += self.bits[word].count_ones() as usize;
+                    word += 1;
+                }
+                len
+            }
+
+            /// Returns `true` if the set contains no values.
+            pub const fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// The values in either set.
+            pub const fn union(self, other: Self) -> Self {
+                #union
+            }
+
+            /// The values in both sets.
+            pub const fn intersection(self, other: Self) -> Self {
+                #intersection
+            }
+
+            /// The values in this set but not in `other`.
+            pub const fn difference(self, other: Self) -> Self {
+                #difference
+            }
+
+            /// The values in exactly one of the sets.
+            pub const fn symmetric_difference(self, other: Self) -> Self {
+                #symmetric_difference
+            }
+
+            /// The values not in this set.
+            pub const fn complement(self) -> Self {
+                #complement
+            }
+
+            /// Returns `true` if every value of this set is in `other`.
+            pub const fn is_subset(&self, other: &Self) -> bool {
+                self.difference(*other).is_empty()
+            }
+
+            /// Returns `true` if every value of `other` is in this set.
+            pub const fn is_superset(&self, other: &Self) -> bool {
+                other.is_subset(self)
+            }
+
+            /// Returns `true` if the sets have no value in common.
+            pub const fn is_disjoint(&self, other: &Self) -> bool {
+                self.intersection(*other).is_empty()
+            }
+
+            /// The values in the set, in declaration order.
+            pub fn iter(&self) -> #iter_ident {
+                #iter_ident { set: *self, bit: 0 }
+            }
+
+            /// The leaf kinds of the values in the set, in declaration order.
+            pub fn kinds(&self) -> impl ::core::iter::Iterator<Item = #kind_ident> + '_ {
+                (0..#len)
+                    .filter(|&bit| self.bits[bit / 64] & (1u64 << (bit % 64)) != 0)
+                    .map(|bit| Self::KINDS[bit])
+            }
+        }
+
+        impl ::core::default::Default for #set_ident {
+            fn default() -> Self {
+                Self::EMPTY
+            }
+        }
+
+        impl ::core::fmt::Debug for #set_ident {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.debug_set().entries(self.kinds()).finish()
+            }
+        }
+
+        impl ::core::convert::From<#kind_ident> for #set_ident {
+            fn from(kind: #kind_ident) -> Self {
+                Self::EMPTY.with(kind)
+            }
+        }
+
+        impl ::core::ops::Not for #set_ident {
+            type Output = Self;
+
+            fn not(self) -> Self {
+                self.complement()
+            }
+        }
+
+        #(#operators)*
+
+        impl ::core::iter::FromIterator<#root_path> for #set_ident {
+            fn from_iter<I: ::core::iter::IntoIterator<Item = #root_path>>(iter: I) -> Self {
+                let mut set = Self::EMPTY;
+                set.extend(iter);
+                set
+            }
+        }
+
+        impl ::core::iter::Extend<#root_path> for #set_ident {
+            fn extend<I: ::core::iter::IntoIterator<Item = #root_path>>(&mut self, iter: I) {
+                for value in iter {
+                    self.insert(value);
+                }
+            }
+        }
+
+        impl ::core::iter::IntoIterator for #set_ident {
+            type Item = #root_path;
+            type IntoIter = #iter_ident;
+
+            fn into_iter(self) -> #iter_ident {
+                self.iter()
+            }
+        }
+
+        impl ::core::iter::IntoIterator for &#set_ident {
+            type Item = #root_path;
+            type IntoIter = #iter_ident;
+
+            fn into_iter(self) -> #iter_ident {
+                self.iter()
+            }
+        }
+
+        #[doc = #iter_doc]
+        #[derive(Debug, Clone)]
+        #vis struct #iter_ident {
+            set: #set_ident,
+            bit: usize,
+        }
+
+        impl ::core::iter::Iterator for #iter_ident {
+            type Item = #root_path;
+
+            fn next(&mut self) -> ::core::option::Option<#root_path> {
+                while self.bit < #len {
+                    let bit = self.bit;
+                    self.bit += 1;
+                    if self.set.bits[bit / 64] & (1u64 << (bit % 64)) != 0 {
+                        return ::core::option::Option::Some(#set_ident::value(bit));
+                    }
+                }
+                ::core::option::Option::None
+            }
+        }
+    })
+}
+
+/// `#[nestum(export)]`: a hidden exported macro describing the tree, so `#[nestum]` in other
+/// crates can nest the enum. Called as `macro! { { callback } { input } }`, it calls
+/// `callback! { input { ... } }` with an entry per enum in the tree, which `Descriptor` parses.
+fn build_export(
+    root: &ItemEnum,
+    options: &EnumOptions,
+    module_path: &str,
+    tree: &Tree,
+) -> proc_macro2::TokenStream {
+    if !options.export {
+        return quote!();
+    }
+
+    let mut enums = vec![(module_path.to_string(), descriptor_item(root))];
+    for child in tree.nodes.iter().filter_map(|node| node.child.as_ref()) {
+        let seen = enums.iter().any(|(module, item)| {
+            *module == child.module_path && item.ident == child.enum_item.ident
+        });
+        if !seen {
+            enums.push((child.module_path.clone(), descriptor_item(&child.enum_item)));
+        }
+    }
+    let modules = enums.iter().map(|(module, _)| module);
+    let items = enums.iter().map(|(_, item)| item);
+    let name = descriptor_macro_ident(&root.ident);
+
+    quote! {
+        #[doc(hidden)]
+        #[macro_export]
+        macro_rules! #name {
+            ({ $($callback:tt)* } { $($input:tt)* }) => {
+                $($callback)*! { $($input)* { #([#modules] { #items })* } }
+            };
+        }
+    }
+}
+
+/// `item` with only the `#[nestum]` attributes that resolving the tree needs.
+fn descriptor_item(item: &ItemEnum) -> ItemEnum {
+    let mut item = item.clone();
+    item.attrs.retain(|attr| attr.path().is_ident("nestum"));
+    for variant in item.variants.iter_mut() {
+        variant.attrs.retain(|attr| attr.path().is_ident("nestum"));
+        for field in variant.fields.iter_mut() {
+            field.attrs.retain(|attr| attr.path().is_ident("nestum"));
+        }
+    }
+    item
+}
+
+fn descriptor_macro_ident(enum_ident: &syn::Ident) -> syn::Ident {
+    format_ident!("__nestum_descriptor_{}", enum_ident)
+}
+
+/// Name of the kind enum of `root_ident`.
+fn kind_ident(root_ident: &syn::Ident, options: &EnumOptions) -> syn::Ident {
+    options
+        .kind
+        .clone()
+        .unwrap_or_else(|| format_ident!("{}Kind", root_ident))
+}
+
+/// The kind enum variant of `node`: its path, concatenated.
+fn kind_variant(node: &TreeNode) -> syn::Ident {
+    format_ident!(
+        "{}",
+        node.path
+            .iter()
+            .map(|ident| ident.to_string())
+            .collect::<String>()
+    )
+}
+
+/// The visitor traits of a root enum (shared, mutable and owning), with one method per leaf,
+/// and the `accept`, `accept_mut` and `fold` methods that dispatch to them.
+fn build_visitor(
+    root: &ItemEnum,
+    options: &EnumOptions,
+    tree: &Tree,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    if !options.visitor {
+        return (quote! {}, quote! {});
+    }
+    let vis = &root.vis;
+    let root_ident = &root.ident;
+    let generics = &root.generics;
+    let where_clause = &root.generics.where_clause;
+    let (impl_generics, ty_generics, _) = root.generics.split_for_impl();
+    let root_path = match &options.companion {
+        Some(_) => quote! { #root_ident },
+        None => quote! { #root_ident::#root_ident },
     };
+    let visitor_ident = format_ident!("{}Visitor", root_ident);
+    let visitor_mut_ident = format_ident!("{}VisitorMut", root_ident);
+    let fold_ident = format_ident!("{}Fold", root_ident);
+    let owned = tree.owned();
+
+    let ref_methods = visitor_methods(tree, LeafAccess::Ref, &root_path, &ty_generics);
+    let visitor_mut_methods = visitor_methods(&owned, LeafAccess::Mut, &root_path, &ty_generics);
+    let fold_methods = visitor_methods(&owned, LeafAccess::Owned, &root_path, &ty_generics);
+    let dispatch = |tree: &Tree, access: LeafAccess, receiver: proc_macro2::TokenStream| {
+        tree.leaf_match(quote! { self }, None, access, &|index| {
+            let method = leaf_method_ident(tree, index);
+            let args = tree
+                .context_bindings(index)
+                .into_iter()
+                .map(|(binding, _, _)| binding)
+                .chain(
+                    tree.leaf_bindings(index)
+                        .into_iter()
+                        .map(|(_, binding)| binding),
+                );
+            quote! { #receiver.#method(#(#args),*) }
+        })
+    };
+    let accept_body = dispatch(tree, LeafAccess::Ref, quote! { visitor });
+    let accept_mut_body = dispatch(&owned, LeafAccess::Mut, quote! { visitor });
+    let fold_body = dispatch(&owned, LeafAccess::Owned, quote! { folder });
+
+    let visitor_doc = format!(
+        "Visits the leaf variant of a `{root_ident}` value by reference; see `accept`. \
+Every method does nothing by default."
+    );
+    let visitor_mut_doc = format!(
+        "Visits the leaf variant of a `{root_ident}` value mutably; see `accept_mut`. Variants \
+nested through `Rc`, `Arc` or another shared pointer are visited as leaves."
+    );
+    let fold_doc = format!(
+        "Rebuilds a `{root_ident}` value from its leaf variant; see `fold`. Every method returns \
+the value it was given by default. Variants nested through `Rc`, `Arc` or another shared \
+pointer are folded as leaves."
+    );
+    let traits = quote! {
+        #[doc = #visitor_doc]
+        // Methods take the fields as declared, e.g. `&String`.
+        #[allow(clippy::ptr_arg)]
+        #vis trait #visitor_ident #generics #where_clause {
+            #(#ref_methods)*
+        }
+
+        #[doc = #visitor_mut_doc]
+        #[allow(clippy::ptr_arg)]
+        #vis trait #visitor_mut_ident #generics #where_clause {
+            #(#visitor_mut_methods)*
+        }
 
-    let Some(outer_info) = resolve_enum_from_path(
-        &module_path,
-        explicit_crate,
-        &outer_enum,
-        current_file,
-        module_root,
-        current_module,
-        enums_by_ident,
-        cache,
-    )?
-    else {
-        return Ok(Pat::TupleStruct(pat_tuple));
+        #[doc = #fold_doc]
+        #vis trait #fold_ident #generics #where_clause {
+            #(#fold_methods)*
+        }
     };
+    let methods = quote! {
+        impl #impl_generics #root_ident #ty_generics #where_clause {
+            /// Calls the `visitor` method for the leaf variant of this value.
+            pub fn accept<V: #visitor_ident #ty_generics + ?Sized>(&self, visitor: &mut V) {
+                #accept_body
+            }
+
+            /// Calls the `visitor` method for the leaf variant of this value, mutably.
+            pub fn accept_mut<V: #visitor_mut_ident #ty_generics + ?Sized>(
+                &mut self,
+                visitor: &mut V,
+            ) {
+                #accept_mut_body
+            }
 
-    let (outer_enum_item, outer_marked) = outer_info;
-    if !outer_marked {
+            /// Replaces this value with what the `folder` method for its leaf variant returns.
+            pub fn fold<F: #fold_ident #ty_generics + ?Sized>(self, folder: &mut F) -> Self {
+                #fold_body
+            }
+        }
+    };
+    (traits, methods)
+}
+
+/// `documents_update` for the leaf at `Documents::Update`.
+fn leaf_method_ident(tree: &Tree, index: usize) -> syn::Ident {
+    let name = tree.nodes[index]
+        .path
+        .iter()
+        .map(|ident| to_snake_case(&ident.to_string()))
+        .collect::<Vec<_>>()
+        .join("_");
+    format_ident!("{}", name)
+}
+
+/// One visitor trait method per leaf of `tree`, taking the context fields above the leaf and
+/// then its own fields as `access` gives them.
+fn visitor_methods(
+    tree: &Tree,
+    access: LeafAccess,
+    root_path: &proc_macro2::TokenStream,
+    ty_generics: &syn::TypeGenerics,
+) -> Vec<proc_macro2::TokenStream> {
+    tree.leaves()
+        .map(|index| {
+            let method = leaf_method_ident(tree, index);
+            let node = &tree.nodes[index];
+            let fields = tree
+                .context_bindings(index)
+                .into_iter()
+                .map(|(_, ty, name)| (ty, name))
+                .chain(
+                    node.field_tys
+                        .iter()
+                        .zip(node.variant.fields.iter())
+                        .map(|(ty, field)| (ty.clone(), field.ident.clone())),
+                );
+            let mut used = HashSet::new();
+            let (names, tys): (Vec<syn::Ident>, Vec<syn::Type>) = fields
+                .enumerate()
+                .map(|(position, (ty, name))| {
+                    let name = name
+                        .filter(|name| used.insert(name.to_string()))
+                        .unwrap_or_else(|| format_ident!("arg{}", position));
+                    (name, ty)
+                })
+                .unzip();
+            let doc = format!("Called for `{}` values.", join_path(&node.path));
+            match access {
+                LeafAccess::Owned => {
+                    let values: Vec<_> = names.iter().map(|name| quote!(#name)).collect();
+                    let value = tree.construct(index, &values, root_path);
+                    quote! {
+                        #[doc = #doc]
+                        fn #method(&mut self, #(#names: #tys),*) -> #root_path #ty_generics {
+                            #value
+                        }
+                    }
+                }
+                LeafAccess::Mut => quote! {
+                    #[doc = #doc]
+                    #[allow(unused_variables)]
+                    fn #method(&mut self, #(#names: &mut #tys),*) {}
+                },
+                _ => quote! {
+                    #[doc = #doc]
+                    #[allow(unused_variables)]
+                    fn #method(&mut self, #(#names: &#tys),*) {}
+                },
+            }
+        })
+        .collect()
+}
+
+/// `#[nestum(paths)]`: `nested_path()`, `nested_path_str()` and `PATHS` on a root enum, naming
+/// its leaf variants by their path through the tree.
+fn build_paths(root: &ItemEnum, options: &EnumOptions, tree: &Tree) -> proc_macro2::TokenStream {
+    if !options.paths {
+        return quote! {};
+    }
+    let segments = |index: usize| {
+        let names = tree.nodes[index].path.iter().map(|ident| ident.to_string());
+        quote! { &[#(#names),*] }
+    };
+    let joined = |index: usize| join_path(&tree.nodes[index].path);
+    let path_body = tree.leaf_match(quote! { self }, None, LeafAccess::None, &segments);
+    let str_body = tree.leaf_match(quote! { self }, None, LeafAccess::None, &|index| {
+        let path = joined(index);
+        quote! { #path }
+    });
+    let paths = tree.leaves().map(joined);
+    let root_ident = &root.ident;
+    let (impl_generics, ty_generics, where_clause) = root.generics.split_for_impl();
+    quote! {
+        impl #impl_generics #root_ident #ty_generics #where_clause {
+            /// The path of every leaf variant, e.g. `"Documents::Update"`, in declaration order.
+            pub const PATHS: &'static [&'static str] = &[#(#paths),*];
+
+            /// The variant names from this enum down to the innermost variant of this value.
+            pub fn nested_path(&self) -> &'static [&'static str] {
+                #path_body
+            }
+
+            /// The nested path of this value joined with `::`, e.g. `"Documents::Update"`.
+            pub fn nested_path_str(&self) -> &'static str {
+                #str_body
+            }
+        }
+    }
+}
+
+/// `#[nestum(values)]`: `all()` and, when no pointer needs allocating, `ALL` on a root enum
+/// whose leaves are all unit variants, listing every value in declaration order.
+fn build_values(
+    root: &ItemEnum,
+    options: &EnumOptions,
+    tree: &Tree,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    if !options.values {
+        return Ok(quote! {});
+    }
+    if let Some(node) = tree.first_non_unit() {
+        return Err(syn::Error::new(
+            root.ident.span(),
+            format!(
+                "#[nestum(values)] requires a tree of unit variants, but {} has fields",
+                join_path(&node.path)
+            ),
+        ));
+    }
+    let values: Vec<_> = tree
+        .leaves()
+        .map(|index| tree.construct(index, &[], &quote! { Self }))
+        .collect();
+    let count = values.len();
+    let root_ident = &root.ident;
+    let (impl_generics, ty_generics, where_clause) = root.generics.split_for_impl();
+    // Allocating a pointer is not const, so trees nesting through one only get `all()`.
+    let is_const = root.generics.params.is_empty()
+        && tree.nodes.iter().all(|node| {
+            node.child
+                .as_ref()
+                .is_none_or(|child| child.pointer.is_none())
+        });
+    let (all_const, all_body) = if is_const {
+        (
+            quote! {
+                /// Every value, in declaration order.
+                pub const ALL: [Self; #count] = [#(#values),*];
+            },
+            quote! { Self::ALL.into_iter() },
+        )
+    } else {
+        (quote! {}, quote! { [#(#values),*].into_iter() })
+    };
+    Ok(quote! {
+        impl #impl_generics #root_ident #ty_generics #where_clause {
+            #all_const
+
+            /// Every value, in declaration order.
+            pub fn all() -> impl ::core::iter::Iterator<Item = Self> {
+                #all_body
+            }
+        }
+    })
+}
+
+/// `Documents::Update` for a variant path.
+fn join_path(path: &[syn::Ident]) -> String {
+    path.iter()
+        .map(|ident| ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// `Display` and `FromStr` impls on a root enum whose leaves are all unit variants, spelling each
+/// value as its renamed path, plus the error type `FromStr` returns.
+fn build_strings(
+    root: &ItemEnum,
+    options: &EnumOptions,
+    tree: &Tree,
+) -> Result<(proc_macro2::TokenStream, proc_macro2::TokenStream), syn::Error> {
+    if !options.display && !options.from_str {
+        return Ok((quote! {}, quote! {}));
+    }
+    let root_ident = &root.ident;
+    if let Some(node) = tree.first_non_unit() {
+        let option = if options.from_str {
+            "from_str"
+        } else {
+            "display"
+        };
+        let hint = if options.from_str {
+            ""
+        } else {
+            "; use #[nestum(paths)] and nested_path_str() to render the path of any value"
+        };
         return Err(syn::Error::new(
-            pat_tuple.span(),
+            root_ident.span(),
             format!(
-                "enum {} is not marked with #[nestum]; \
-only #[nestum] enums support nested match patterns",
-                outer_enum
+                "#[nestum({option})] requires a tree of unit variants, but {} has fields{hint}",
+                join_path(&node.path)
             ),
         ));
     }
 
-    let Some((inner_enum_ident, inner_enum_path, inner_explicit_crate)) =
-        resolve_inner_enum_path(&outer_enum_item, &outer_variant, enums_by_ident)?
-    else {
-        return Ok(Pat::TupleStruct(pat_tuple));
-    };
+    let names = tree.spellings(options, root_ident)?;
 
-    let inner_enum_info = resolve_enum_from_path(
-        &inner_enum_path,
-        inner_explicit_crate,
-        &inner_enum_ident,
-        current_file,
-        module_root,
-        current_module,
-        enums_by_ident,
-        cache,
-    )?;
+    let (impl_generics, ty_generics, where_clause) = root.generics.split_for_impl();
+    let mut items = quote! {};
+    let mut impls = quote! {};
+    if options.display {
+        let body = tree.leaf_match(quote! { self }, None, LeafAccess::None, &|index| {
+            let name = &names[index];
+            quote! { #name }
+        });
+        impls.extend(quote! {
+            impl #impl_generics ::core::fmt::Display for #root_ident #ty_generics #where_clause {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.write_str(#body)
+                }
+            }
+        });
+    }
+    if options.from_str {
+        let vis = &root.vis;
+        let error_ident = format_ident!("Parse{}Error", root_ident);
+        let leaves: Vec<usize> = tree.leaves().collect();
+        let strings = leaves.iter().map(|&index| &names[index]);
+        let values = leaves
+            .iter()
+            .map(|&index| tree.construct(index, &[], &quote! { Self }));
+        let expected = strings.clone();
+        let error_doc = format!("The error returned when parsing a `{root_ident}` fails.");
+        items.extend(quote! {
+            #[doc = #error_doc]
+            #[derive(Debug, Clone, PartialEq, Eq)]
+            #vis struct #error_ident {
+                input: ::std::string::String,
+            }
 
-    let (inner_enum_item, inner_marked) = inner_enum_info.ok_or_else(|| {
-        syn::Error::new(
-            pat_tuple.span(),
-            format!(
-                "inner enum {} not found for {}::{}; \
-ensure it is declared in the referenced module",
-                inner_enum_ident, outer_enum, outer_variant
-            ),
-        )
-    })?;
-    if !inner_marked {
+            impl #error_ident {
+                /// The string that failed to parse.
+                pub fn input(&self) -> &str {
+                    &self.input
+                }
+
+                /// Every string that parses, in declaration order.
+                pub fn expected(&self) -> &'static [&'static str] {
+                    &[#(#expected),*]
+                }
+            }
+
+            impl ::core::fmt::Display for #error_ident {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    ::core::write!(
+                        f,
+                        "unknown {} \"{}\"; expected one of: {}",
+                        ::core::stringify!(#root_ident),
+                        self.input,
+                        self.expected().join(", ")
+                    )
+                }
+            }
+
+            impl ::std::error::Error for #error_ident {}
+        });
+        impls.extend(quote! {
+            impl #impl_generics ::core::str::FromStr for #root_ident #ty_generics #where_clause {
+                type Err = #error_ident;
+
+                fn from_str(input: &str) -> ::core::result::Result<Self, Self::Err> {
+                    match input {
+                        #(#strings => ::core::result::Result::Ok(#values),)*
+                        _ => ::core::result::Result::Err(#error_ident {
+                            input: ::std::string::String::from(input),
+                        }),
+                    }
+                }
+            }
+        });
+    }
+    Ok((items, impls))
+}
+
+/// `Serialize` and `Deserialize` impls on a root enum that tag each value with the spelled path
+/// of its leaf and put the leaf's fields next to the tag, e.g.
+/// `{"type": "documents.update", "data": ...}`.
+fn build_serde(
+    root: &ItemEnum,
+    options: &EnumOptions,
+    tree: &Tree,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    if !options.serde {
+        return Ok(quote! {});
+    }
+    let root_ident = &root.ident;
+    if let Some(node) = tree.nodes.iter().find(|node| {
+        node.child
+            .as_ref()
+            .is_some_and(|child| child.fields.len() > 1)
+    }) {
         return Err(syn::Error::new(
-            pat_tuple.span(),
+            root_ident.span(),
             format!(
-                "inner enum {} is not marked with #[nestum]; \
-only #[nestum] enums support nested match patterns",
-                inner_enum_ident
+                "#[nestum(serde)] does not support context fields, but {} has fields next to \
+its nested enum",
+                join_path(&node.path)
             ),
         ));
     }
+    let names = tree.spellings(options, root_ident)?;
+    let tag = options.tag.as_deref().unwrap_or("type");
+    let content = options.content.as_deref().unwrap_or("data");
+    let root_name = root_ident.to_string();
+    let leaves: Vec<usize> = tree.leaves().collect();
+    let variants: Vec<&String> = leaves.iter().map(|&index| &names[index]).collect();
+
+    let serialize_body = tree.leaf_match(quote! { self }, None, LeafAccess::Ref, &|index| {
+        let name = &names[index];
+        let bindings: Vec<syn::Ident> = tree
+            .leaf_bindings(index)
+            .into_iter()
+            .map(|(_, binding)| binding)
+            .collect();
+        let data = match (&tree.nodes[index].variant.fields, bindings.as_slice()) {
+            (_, []) => quote! {
+                ::serde::ser::SerializeStruct::skip_field(&mut state, #content)?;
+            },
+            (Fields::Unnamed(_), [binding]) => quote! {
+                ::serde::ser::SerializeStruct::serialize_field(&mut state, #content, #binding)?;
+            },
+            (Fields::Unnamed(_), _) => quote! {
+                ::serde::ser::SerializeStruct::serialize_field(
+                    &mut state,
+                    #content,
+                    &(#(#bindings,)*),
+                )?;
+            },
+            _ => {
+                let helper = serialize_named_data(tree, index);
+                quote! {
+                    #helper
+                    ::serde::ser::SerializeStruct::serialize_field(
+                        &mut state,
+                        #content,
+                        &__NestumData(#(#bindings),*),
+                    )?;
+                }
+            }
+        };
+        let len = if bindings.is_empty() { 1usize } else { 2 };
+        quote! {{
+            let mut state = ::serde::Serializer::serialize_struct(serializer, #root_name, #len)?;
+            ::serde::ser::SerializeStruct::serialize_field(&mut state, #tag, #name)?;
+            #data
+            ::serde::ser::SerializeStruct::end(state)
+        }}
+    });
 
-    ensure_inner_variant_exists(&inner_enum_item, &inner_variant)?;
+    let mut helpers = Vec::new();
+    // The tag comes first, so the content is read in place once it arrives, or not at all for
+    // unit leaves.
+    let mut map_arms = Vec::new();
+    let mut missing_arms = Vec::new();
+    let mut seq_arms = Vec::new();
+    for &index in leaves.iter() {
+        let name = &names[index];
+        let bindings: Vec<syn::Ident> = tree
+            .leaf_bindings(index)
+            .into_iter()
+            .map(|(_, binding)| binding)
+            .collect();
+        let values: Vec<_> = bindings.iter().map(|binding| quote!(#binding)).collect();
+        let value = tree.construct(index, &values, &quote! { #root_ident });
+        let pat = match (&tree.nodes[index].variant.fields, bindings.as_slice()) {
+            (_, []) => {
+                map_arms.push(quote! {
+                    #name => {
+                        ::serde::de::MapAccess::next_value::<::serde::de::IgnoredAny>(&mut map)?;
+                        #value
+                    }
+                });
+                missing_arms.push(quote! { #name => #value, });
+                seq_arms.push(quote! { #name => #value, });
+                continue;
+            }
+            (Fields::Unnamed(_), [binding]) => quote! { #binding },
+            (Fields::Unnamed(_), _) => quote! { (#(#bindings,)*) },
+            _ => {
+                let helper = format_ident!("__NestumData{}", index);
+                helpers.push(deserialize_named_data(tree, index, &helper));
+                quote! { #helper(#(#bindings),*) }
+            }
+        };
+        map_arms.push(quote! {
+            #name => {
+                let #pat = ::serde::de::MapAccess::next_value(&mut map)?;
+                #value
+            }
+        });
+        missing_arms.push(quote! {
+            #name => {
+                return ::core::result::Result::Err(
+                    <A::Error as ::serde::de::Error>::missing_field(#content),
+                );
+            }
+        });
+        seq_arms.push(quote! {
+            #name => {
+                let #pat = match ::serde::de::SeqAccess::next_element(&mut seq)? {
+                    ::core::option::Option::Some(data) => data,
+                    ::core::option::Option::None => {
+                        return ::core::result::Result::Err(
+                            <A::Error as ::serde::de::Error>::invalid_length(1, &self),
+                        );
+                    }
+                };
+                #value
+            }
+        });
+    }
 
-    let outer_module_idents = effective_module_idents(&module_path, explicit_crate, current_module);
-    let inner_module_idents = if inner_enum_path.is_empty() {
-        outer_module_idents.clone()
-    } else {
-        effective_module_idents(&inner_enum_path, inner_explicit_crate, current_module)
+    let type_params: Vec<&syn::Ident> = root
+        .generics
+        .type_params()
+        .map(|param| &param.ident)
+        .collect();
+    let mut ser_generics = root.generics.clone();
+    let mut de_generics = root.generics.clone();
+    de_generics.params.insert(0, syn::parse_quote!('de));
+    for param in type_params.iter() {
+        ser_generics
+            .make_where_clause()
+            .predicates
+            .push(syn::parse_quote!(#param: ::serde::Serialize));
+        de_generics
+            .make_where_clause()
+            .predicates
+            .push(syn::parse_quote!(#param: ::serde::Deserialize<'de>));
+    }
+    let (ser_impl_generics, ty_generics, ser_where_clause) = ser_generics.split_for_impl();
+    let (de_impl_generics, _, de_where_clause) = de_generics.split_for_impl();
+    let generics = &root.generics;
+    let where_clause = &root.generics.where_clause;
+    let expecting = format!("a {root_name} tagged with `{tag}`");
+    let content_first =
+        format!("`{content}` must come after `{tag}` when deserializing {root_name}");
+    let unknown_variant = quote! {
+        return ::core::result::Result::Err(
+            <A::Error as ::serde::de::Error>::unknown_variant(&tag, &[#(#variants),*]),
+        )
     };
-    let outer_variant_path = build_path_from_idents(
-        outer_module_idents,
-        &[
-            outer_enum.clone(),
-            outer_enum.clone(),
-            outer_variant.clone(),
-        ],
-    );
-    let inner_variant_path = build_path_from_idents(
-        inner_module_idents,
-        &[
-            inner_enum_ident.clone(),
-            inner_enum_ident.clone(),
-            inner_variant,
-        ],
-    );
-    let elems = pat_tuple.elems;
 
-    let inner_pat = Pat::TupleStruct(PatTupleStruct {
-        attrs: Vec::new(),
+    Ok(quote! {
+        impl #ser_impl_generics ::serde::Serialize for #root_ident #ty_generics #ser_where_clause {
+            fn serialize<S: ::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> ::core::result::Result<S::Ok, S::Error> {
+                #serialize_body
+            }
+        }
+
+        impl #de_impl_generics ::serde::Deserialize<'de> for #root_ident #ty_generics #de_where_clause {
+            fn deserialize<D: ::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<Self, D::Error> {
+                #(#helpers)*
+
+                struct __NestumVisitor #generics (
+                    ::core::marker::PhantomData<fn() -> #root_ident #ty_generics>,
+                ) #where_clause;
+
+                impl #de_impl_generics ::serde::de::Visitor<'de> for __NestumVisitor #ty_generics
+                #de_where_clause
+                {
+                    type Value = #root_ident #ty_generics;
+
+                    fn expecting(
+                        &self,
+                        f: &mut ::core::fmt::Formatter<'_>,
+                    ) -> ::core::fmt::Result {
+                        f.write_str(#expecting)
+                    }
+
+                    fn visit_map<A: ::serde::de::MapAccess<'de>>(
+                        self,
+                        mut map: A,
+                    ) -> ::core::result::Result<Self::Value, A::Error> {
+                        let mut __nestum_tag = ::core::option::Option::None;
+                        while let ::core::option::Option::Some(key) =
+                            ::serde::de::MapAccess::next_key::<::std::string::String>(&mut map)?
+                        {
+                            if key == #tag {
+                                if __nestum_tag.is_some() {
+                                    return ::core::result::Result::Err(
+                                        <A::Error as ::serde::de::Error>::duplicate_field(#tag),
+                                    );
+                                }
+                                __nestum_tag = ::core::option::Option::Some(
+                                    ::serde::de::MapAccess::next_value::<::std::string::String>(&mut map)?,
+                                );
+                            } else if key == #content {
+                                let ::core::option::Option::Some(tag) = __nestum_tag else {
+                                    return ::core::result::Result::Err(
+                                        <A::Error as ::serde::de::Error>::custom(#content_first),
+                                    );
+                                };
+                                let value = match tag.as_str() {
+                                    #(#map_arms)*
+                                    _ => #unknown_variant,
+                                };
+                                while ::serde::de::MapAccess::next_key::<::serde::de::IgnoredAny>(&mut map)?
+                                    .is_some()
+                                {
+                                    ::serde::de::MapAccess::next_value::<::serde::de::IgnoredAny>(&mut map)?;
+                                }
+                                return ::core::result::Result::Ok(value);
+                            } else {
+                                ::serde::de::MapAccess::next_value::<::serde::de::IgnoredAny>(&mut map)?;
+                            }
+                        }
+                        let ::core::option::Option::Some(tag) = __nestum_tag else {
+                            return ::core::result::Result::Err(
+                                <A::Error as ::serde::de::Error>::missing_field(#tag),
+                            );
+                        };
+                        ::core::result::Result::Ok(match tag.as_str() {
+                            #(#missing_arms)*
+                            _ => #unknown_variant,
+                        })
+                    }
+
+                    fn visit_seq<A: ::serde::de::SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> ::core::result::Result<Self::Value, A::Error> {
+                        let tag: ::std::string::String =
+                            match ::serde::de::SeqAccess::next_element(&mut seq)? {
+                                ::core::option::Option::Some(tag) => tag,
+                                ::core::option::Option::None => {
+                                    return ::core::result::Result::Err(
+                                        <A::Error as ::serde::de::Error>::invalid_length(0, &self),
+                                    );
+                                }
+                            };
+                        ::core::result::Result::Ok(match tag.as_str() {
+                            #(#seq_arms)*
+                            _ => {
+                                return ::core::result::Result::Err(
+                                    <A::Error as ::serde::de::Error>::unknown_variant(
+                                        &tag,
+                                        &[#(#variants),*],
+                                    ),
+                                );
+                            }
+                        })
+                    }
+                }
+
+                ::serde::Deserializer::deserialize_struct(
+                    deserializer,
+                    #root_name,
+                    &[#tag, #content],
+                    __NestumVisitor(::core::marker::PhantomData),
+                )
+            }
+        }
+    })
+}
+
+/// Names of the leaf's fields as serialized, and type parameters for a helper holding them.
+fn named_data_fields(tree: &Tree, index: usize) -> (Vec<String>, Vec<syn::Ident>) {
+    tree.nodes[index]
+        .variant
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let key = field
+                .ident
+                .as_ref()
+                .map(|ident| syn::ext::IdentExt::unraw(ident).to_string())
+                .unwrap_or_default();
+            (key, format_ident!("__T{}", i))
+        })
+        .unzip()
+}
+
+/// `__NestumData`, which serializes the borrowed fields of a struct leaf as a struct.
+fn serialize_named_data(tree: &Tree, index: usize) -> proc_macro2::TokenStream {
+    let (keys, params) = named_data_fields(tree, index);
+    let variant_name = tree.nodes[index].variant.ident.to_string();
+    let members = (0..keys.len()).map(syn::Index::from);
+    let len = keys.len();
+    quote! {
+        struct __NestumData<'a, #(#params),*>(#(&'a #params),*);
+
+        impl<#(#params: ::serde::Serialize),*> ::serde::Serialize for __NestumData<'_, #(#params),*> {
+            fn serialize<S: ::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> ::core::result::Result<S::Ok, S::Error> {
+                let mut state = ::serde::Serializer::serialize_struct(serializer, #variant_name, #len)?;
+                #(::serde::ser::SerializeStruct::serialize_field(&mut state, #keys, self.#members)?;)*
+                ::serde::ser::SerializeStruct::end(state)
+            }
+        }
+    }
+}
+
+/// `helper`, which deserializes the fields of a struct leaf from a struct.
+fn deserialize_named_data(
+    tree: &Tree,
+    index: usize,
+    helper: &syn::Ident,
+) -> proc_macro2::TokenStream {
+    let (keys, params) = named_data_fields(tree, index);
+    let variant_name = tree.nodes[index].variant.ident.to_string();
+    let expecting = format!("struct variant {}", join_path(&tree.nodes[index].path));
+    let bindings: Vec<syn::Ident> = (0..keys.len())
+        .map(|i| format_ident!("__nestum_{}", i))
+        .collect();
+    let positions = 0..keys.len();
+    quote! {
+        struct #helper<#(#params),*>(#(#params),*);
+
+        impl<'de, #(#params: ::serde::Deserialize<'de>),*> ::serde::Deserialize<'de>
+            for #helper<#(#params),*>
+        {
+            fn deserialize<D: ::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::core::result::Result<Self, D::Error> {
+                struct __NestumDataVisitor<#(#params),*>(
+                    ::core::marker::PhantomData<fn() -> (#(#params,)*)>,
+                );
+
+                impl<'de, #(#params: ::serde::Deserialize<'de>),*> ::serde::de::Visitor<'de>
+                    for __NestumDataVisitor<#(#params),*>
+                {
+                    type Value = #helper<#(#params),*>;
+
+                    fn expecting(
+                        &self,
+                        f: &mut ::core::fmt::Formatter<'_>,
+                    ) -> ::core::fmt::Result {
+                        f.write_str(#expecting)
+                    }
+
+                    fn visit_map<A: ::serde::de::MapAccess<'de>>(
+                        self,
+                        mut map: A,
+                    ) -> ::core::result::Result<Self::Value, A::Error> {
+                        #(let mut #bindings = ::core::option::Option::None;)*
+                        while let ::core::option::Option::Some(key) =
+                            ::serde::de::MapAccess::next_key::<::std::string::String>(&mut map)?
+                        {
+                            match key.as_str() {
+                                #(#keys => {
+                                    if #bindings.is_some() {
+                                        return ::core::result::Result::Err(
+                                            <A::Error as ::serde::de::Error>::duplicate_field(#keys),
+                                        );
+                                    }
+                                    #bindings = ::core::option::Option::Some(
+                                        ::serde::de::MapAccess::next_value(&mut map)?,
+                                    );
+                                })*
+                                _ => {
+                                    ::serde::de::MapAccess::next_value::<::serde::de::IgnoredAny>(
+                                        &mut map,
+                                    )?;
+                                }
+                            }
+                        }
+                        ::core::result::Result::Ok(#helper(#(match #bindings {
+                            ::core::option::Option::Some(value) => value,
+                            ::core::option::Option::None => {
+                                return ::core::result::Result::Err(
+                                    <A::Error as ::serde::de::Error>::missing_field(#keys),
+                                );
+                            }
+                        }),*))
+                    }
+
+                    fn visit_seq<A: ::serde::de::SeqAccess<'de>>(
+                        self,
+                        mut seq: A,
+                    ) -> ::core::result::Result<Self::Value, A::Error> {
+                        ::core::result::Result::Ok(#helper(#(
+                            match ::serde::de::SeqAccess::next_element(&mut seq)? {
+                                ::core::option::Option::Some(value) => value,
+                                ::core::option::Option::None => {
+                                    return ::core::result::Result::Err(
+                                        <A::Error as ::serde::de::Error>::invalid_length(
+                                            #positions,
+                                            &self,
+                                        ),
+                                    );
+                                }
+                            }
+                        ),*))
+                    }
+                }
+
+                ::serde::Deserializer::deserialize_struct(
+                    deserializer,
+                    #variant_name,
+                    &[#(#keys),*],
+                    __NestumDataVisitor(::core::marker::PhantomData),
+                )
+            }
+        }
+    }
+}
+
+/// Generic parameters for a generated constructor fn: the enum's parameters without defaults,
+/// which are only allowed on type definitions.
+fn constructor_generics(generics: &syn::Generics) -> syn::Generics {
+    let mut generics = generics.clone();
+    generics.where_clause = None;
+    for param in generics.params.iter_mut() {
+        match param {
+            syn::GenericParam::Type(ty) => {
+                ty.eq_token = None;
+                ty.default = None;
+            }
+            syn::GenericParam::Const(konst) => {
+                konst.eq_token = None;
+                konst.default = None;
+            }
+            syn::GenericParam::Lifetime(_) => {}
+        }
+    }
+    generics
+}
+
+/// Substitutes an inner enum's generic parameters with the arguments an outer variant
+/// supplies for them.
+struct GenericSubst {
+    types: HashMap<String, syn::Type>,
+    lifetimes: HashMap<String, syn::Lifetime>,
+    consts: HashMap<String, syn::Expr>,
+}
+
+impl GenericSubst {
+    fn identity() -> Self {
+        GenericSubst {
+            types: HashMap::new(),
+            lifetimes: HashMap::new(),
+            consts: HashMap::new(),
+        }
+    }
+
+    fn new(params: &syn::Generics, applied: &syn::Type) -> Result<Self, syn::Error> {
+        let mut subst = GenericSubst::identity();
+        if params.params.is_empty() {
+            return Ok(subst);
+        }
+
+        let mut lifetime_args = Vec::new();
+        let mut other_args = Vec::new();
+        if let syn::Type::Path(type_path) = applied
+            && let Some(segment) = type_path.path.segments.last()
+            && let syn::PathArguments::AngleBracketed(args) = &segment.arguments
+        {
+            for arg in args.args.iter() {
+                match arg {
+                    syn::GenericArgument::Lifetime(lifetime) => {
+                        lifetime_args.push(lifetime.clone())
+                    }
+                    other => other_args.push(other.clone()),
+                }
+            }
+        }
+
+        let mut lifetime_args = lifetime_args.into_iter();
+        let mut other_args = other_args.into_iter();
+        for param in params.params.iter() {
+            match param {
+                syn::GenericParam::Lifetime(param) => {
+                    let arg = lifetime_args
+                        .next()
+                        .ok_or_else(|| missing_generic_arg(applied, &param.lifetime.to_string()))?;
+                    subst
+                        .lifetimes
+                        .insert(param.lifetime.ident.to_string(), arg);
+                }
+                syn::GenericParam::Type(param) => {
+                    let arg = match other_args.next() {
+                        Some(syn::GenericArgument::Type(ty)) => ty,
+                        Some(other) => {
+                            return Err(syn::Error::new(
+                                other.span(),
+                                format!("expected a type argument for {}", param.ident),
+                            ));
+                        }
+                        None => match &param.default {
+                            Some(default) => subst.apply(default),
+                            None => {
+                                return Err(missing_generic_arg(applied, &param.ident.to_string()));
+                            }
+                        },
+                    };
+                    subst.types.insert(param.ident.to_string(), arg);
+                }
+                syn::GenericParam::Const(param) => {
+                    let arg = match other_args.next() {
+                        Some(syn::GenericArgument::Const(expr)) => expr,
+                        Some(syn::GenericArgument::Type(syn::Type::Path(path))) => {
+                            syn::Expr::Path(syn::ExprPath {
+                                attrs: Vec::new(),
+                                qself: path.qself,
+                                path: path.path,
+                            })
+                        }
+                        Some(other) => {
+                            return Err(syn::Error::new(
+                                other.span(),
+                                format!("expected a const argument for {}", param.ident),
+                            ));
+                        }
+                        None => match &param.default {
+                            Some(default) => default.clone(),
+                            None => {
+                                return Err(missing_generic_arg(applied, &param.ident.to_string()));
+                            }
+                        },
+                    };
+                    subst.consts.insert(param.ident.to_string(), arg);
+                }
+            }
+        }
+
+        Ok(subst)
+    }
+
+    fn apply(&self, ty: &syn::Type) -> syn::Type {
+        let mut ty = ty.clone();
+        SubstVisitor(self).visit_type_mut(&mut ty);
+        ty
+    }
+
+    fn param_ident(path: &syn::Path) -> Option<String> {
+        if path.leading_colon.is_none()
+            && path.segments.len() == 1
+            && path.segments[0].arguments.is_none()
+        {
+            Some(path.segments[0].ident.to_string())
+        } else {
+            None
+        }
+    }
+}
+
+struct SubstVisitor<'a>(&'a GenericSubst);
+
+impl VisitMut for SubstVisitor<'_> {
+    fn visit_type_mut(&mut self, ty: &mut syn::Type) {
+        if let syn::Type::Path(type_path) = ty
+            && type_path.qself.is_none()
+            && let Some(ident) = GenericSubst::param_ident(&type_path.path)
+            && let Some(replacement) = self.0.types.get(&ident)
+        {
+            *ty = replacement.clone();
+            return;
+        }
+        syn::visit_mut::visit_type_mut(self, ty);
+    }
+
+    fn visit_generic_argument_mut(&mut self, arg: &mut syn::GenericArgument) {
+        if let syn::GenericArgument::Type(syn::Type::Path(type_path)) = arg
+            && type_path.qself.is_none()
+            && let Some(ident) = GenericSubst::param_ident(&type_path.path)
+            && let Some(replacement) = self.0.consts.get(&ident)
+        {
+            *arg = syn::GenericArgument::Const(const_arg_expr(replacement));
+            return;
+        }
+        syn::visit_mut::visit_generic_argument_mut(self, arg);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        if let syn::Expr::Path(expr_path) = expr
+            && expr_path.qself.is_none()
+            && let Some(ident) = GenericSubst::param_ident(&expr_path.path)
+            && let Some(replacement) = self.0.consts.get(&ident)
+        {
+            *expr = const_arg_expr(replacement);
+            return;
+        }
+        syn::visit_mut::visit_expr_mut(self, expr);
+    }
+
+    fn visit_lifetime_mut(&mut self, lifetime: &mut syn::Lifetime) {
+        if let Some(replacement) = self.0.lifetimes.get(&lifetime.ident.to_string()) {
+            *lifetime = replacement.clone();
+        }
+    }
+}
+
+/// Const arguments that are not literals or paths must be wrapped in braces.
+fn const_arg_expr(expr: &syn::Expr) -> syn::Expr {
+    match expr {
+        syn::Expr::Lit(_) | syn::Expr::Path(_) | syn::Expr::Block(_) => expr.clone(),
+        other => syn::parse_quote!({ #other }),
+    }
+}
+
+fn missing_generic_arg(applied: &syn::Type, param: &str) -> syn::Error {
+    syn::Error::new(
+        applied.span(),
+        format!(
+            "missing generic argument for {param}; \
+nested enum fields must spell out the inner enum's generic arguments"
+        ),
+    )
+}
+
+fn rewrite_variant_type_for_nested(
+    variant: &mut syn::Variant,
+    child: &NestedChild,
+) -> Result<(), syn::Error> {
+    let Some(field) = variant.fields.iter_mut().nth(child.index) else {
+        return Ok(());
+    };
+
+    let ty = inner_enum_type(child, &child.field_ty);
+    field.ty = match &child.pointer {
+        Some(_) => Pointer::rewrap_type(&field.ty, ty),
+        None => ty,
+    };
+    Ok(())
+}
+
+/// The absolute inner enum type of `child`, with the generic arguments of `field_ty` (the field
+/// type as written, or mapped onto the root enum's parameters).
+fn inner_enum_type(child: &NestedChild, field_ty: &syn::Type) -> syn::Type {
+    let mut type_path = child.enum_path();
+    if let syn::Type::Path(original) = field_ty
+        && let Some(original_last) = original.path.segments.last()
+        && let Some(last) = type_path.segments.last_mut()
+    {
+        last.arguments = original_last.arguments.clone();
+    }
+    syn::Type::Path(syn::TypePath {
         qself: None,
-        path: inner_variant_path,
-        paren_token: pat_tuple.paren_token,
-        elems,
+        path: type_path,
+    })
+}
+
+/// Absolute path to a `#[nestum]` enum type: inside its shadow module (`crate::m::Outer::Outer`),
+/// or at its own path in companion mode.
+fn enum_type_path(module_path: &str, item: &ItemEnum) -> syn::Path {
+    let module_idents = absolute_module_idents(module_path);
+    let mut tail = vec![item.ident.clone()];
+    if !EnumOptions::from_attrs(item).is_ok_and(|options| options.companion.is_some()) {
+        tail.push(item.ident.clone());
+    }
+    let mut path = build_path_from_idents(module_idents, &tail);
+    if module_path.starts_with("::") {
+        path.leading_colon = Some(Default::default());
+    }
+    path
+}
+
+/// Sub-patterns that sit behind a smart pointer. The enclosing pattern binds the pointer itself;
+/// each recorded pattern is then matched against the pointee, first in the arm's guard to decide
+/// whether the arm applies, then again in the body to bind its variables (as references).
+#[derive(Default)]
+struct PointerMatches {
+    matches: Vec<PointerMatch>,
+}
+
+struct PointerMatch {
+    binding: syn::Ident,
+    /// Reference to the pointee, e.g. `&Inner<'_, _>`, which the pointer derefs to.
+    target: syn::Type,
+    pat: Pat,
+}
+
+impl PointerMatches {
+    fn is_empty(&self) -> bool {
+        self.matches.is_empty()
+    }
+
+    /// Records `pat` as the pointee pattern and returns the binding that replaces it.
+    fn bind(&mut self, target: syn::Type, pat: Pat) -> Pat {
+        let binding = format_ident!("__nestum_ptr{}", self.matches.len());
+        // Inner levels are wrapped first, so insert at the front to keep parents first.
+        self.matches.insert(
+            0,
+            PointerMatch {
+                binding: binding.clone(),
+                target,
+                pat,
+            },
+        );
+        Pat::Ident(syn::PatIdent {
+            attrs: Vec::new(),
+            by_ref: None,
+            mutability: None,
+            ident: binding,
+            subpat: None,
+        })
+    }
+
+    /// A guard that holds when every pointee matches and then `guard` holds.
+    fn guard(&self, guard: Option<syn::Expr>) -> Option<syn::Expr> {
+        if self.is_empty() {
+            return guard;
+        }
+        let check = guard.unwrap_or_else(|| syn::parse_quote!(true));
+        Some(self.matches.iter().rev().fold(check, |check, matched| {
+            let PointerMatch {
+                binding,
+                target,
+                pat,
+            } = matched;
+            syn::parse_quote! {{
+                let __nestum_target: #target = &#binding;
+                match __nestum_target {
+                    #[allow(unused_variables)]
+                    #pat => #check,
+                    #[allow(unreachable_patterns)]
+                    _ => false,
+                }
+            }}
+        }))
+    }
+
+    /// Statements binding the pointees' variables, running `otherwise` when one does not match;
+    /// without it, the guard has already checked they match.
+    fn lets(&self, otherwise: Option<&syn::Expr>) -> Vec<syn::Stmt> {
+        let otherwise = match otherwise {
+            Some(otherwise) => quote!(#otherwise),
+            None => quote!(::core::unreachable!(
+                "the guard already matched this pointee"
+            )),
+        };
+        self.matches
+            .iter()
+            .flat_map(|matched| {
+                let PointerMatch {
+                    binding,
+                    target,
+                    pat,
+                } = matched;
+                let stmts: [syn::Stmt; 2] = [
+                    syn::parse_quote!(let __nestum_target: #target = &#binding;),
+                    syn::parse_quote! {
+                        #[allow(irrefutable_let_patterns)]
+                        let #pat = __nestum_target else { #otherwise };
+                    },
+                ];
+                stmts
+            })
+            .collect()
+    }
+
+    fn body(&self, body: syn::Expr) -> syn::Expr {
+        if self.is_empty() {
+            return body;
+        }
+        let lets = self.lets(None);
+        syn::parse_quote!({ #(#lets)* #body })
+    }
+}
+
+/// Rewrites the pattern of a match arm (or anything lowered to one). An or-pattern whose
+/// alternatives go through a pointer is split into one arm per alternative, since each needs its
+/// own guard.
+fn rewrite_arm_pat(
+    pat: Pat,
+    resolver: &mut Resolver,
+) -> Result<Vec<(Pat, PointerMatches)>, syn::Error> {
+    let Pat::Or(pat_or) = pat else {
+        let mut pointers = PointerMatches::default();
+        let pat = rewrite_pat(pat, resolver, &mut pointers)?;
+        return Ok(vec![(pat, pointers)]);
+    };
+
+    let mut cases = Vec::new();
+    for case in pat_or.cases {
+        let mut pointers = PointerMatches::default();
+        let case = rewrite_pat(case, resolver, &mut pointers)?;
+        cases.push((case, pointers));
+    }
+    if cases.iter().any(|(_, pointers)| !pointers.is_empty()) {
+        return Ok(cases);
+    }
+    let pat = Pat::Or(syn::PatOr {
+        attrs: pat_or.attrs,
+        leading_vert: pat_or.leading_vert,
+        cases: cases.into_iter().map(|(case, _)| case).collect(),
     });
+    Ok(vec![(pat, PointerMatches::default())])
+}
 
-    Ok(Pat::TupleStruct(PatTupleStruct {
-        attrs: Vec::new(),
-        qself: None,
-        path: outer_variant_path,
-        paren_token: pat_tuple.paren_token,
-        elems: Punctuated::from_iter(std::iter::once(inner_pat)),
-    }))
+/// Rewrites nested paths anywhere in `pat`, including sub-patterns of tuples, references,
+/// bindings, slices and the fields of ordinary tuple-struct and struct patterns. Levels behind a
+/// smart pointer are bound to a variable and recorded in `pointers`.
+fn rewrite_pat(
+    pat: Pat,
+    resolver: &mut Resolver,
+    pointers: &mut PointerMatches,
+) -> Result<Pat, syn::Error> {
+    match pat {
+        Pat::Path(mut pat_path) => {
+            let Some(nested) = resolve_nested_pat_path(&pat_path.path, resolver)? else {
+                return Ok(Pat::Path(pat_path));
+            };
+            pat_path.path = nested.leaf_path();
+            if nested.leaf_is_subtree {
+                // A bare path to a nested variant matches its whole subtree: `Event::Documents`
+                // becomes `Event::Documents(..)`, or `Event::Documents { .. }`.
+                let attrs = &pat_path.attrs;
+                let path = &pat_path.path;
+                let subtree = match nested.leaf_fields {
+                    Fields::Named(_) => syn::parse_quote!(#(#attrs)* #path { .. }),
+                    _ => syn::parse_quote!(#(#attrs)* #path(..)),
+                };
+                return Ok(nested.wrap(subtree, Vec::new(), pointers));
+            }
+            Ok(nested.wrap(Pat::Path(pat_path), Vec::new(), pointers))
+        }
+        Pat::TupleStruct(mut pat_tuple) => {
+            pat_tuple.elems = rewrite_pats(pat_tuple.elems, resolver, pointers)?;
+            let Some(nested) = resolve_nested_pat_path(&pat_tuple.path, resolver)? else {
+                return Ok(Pat::TupleStruct(pat_tuple));
+            };
+            pat_tuple.path = nested.leaf_path();
+            let context = nested.split_context_pats(&mut pat_tuple)?;
+            let leaf = match &nested.leaf_fields {
+                Fields::Unit if pat_tuple.elems.is_empty() => Pat::Path(syn::PatPath {
+                    attrs: pat_tuple.attrs,
+                    qself: pat_tuple.qself,
+                    path: pat_tuple.path,
+                }),
+                // With context fields, a struct leaf's fields may be given in order too.
+                Fields::Named(fields) if !context.is_empty() => {
+                    let attrs = &pat_tuple.attrs;
+                    let path = &pat_tuple.path;
+                    let idents = fields.named.iter().map(|field| &field.ident);
+                    let elems = &pat_tuple.elems;
+                    let elems = elems.iter();
+                    syn::parse_quote!(#(#attrs)* #path { #(#idents: #elems),* })
+                }
+                _ => Pat::TupleStruct(pat_tuple),
+            };
+            Ok(nested.wrap(leaf, context, pointers))
+        }
+        Pat::Struct(mut pat_struct) => {
+            for field in pat_struct.fields.iter_mut() {
+                rewrite_pat_in_place(&mut field.pat, resolver, pointers)?;
+            }
+            let Some(nested) = resolve_nested_pat_path(&pat_struct.path, resolver)? else {
+                return Ok(Pat::Struct(pat_struct));
+            };
+            pat_struct.path = nested.leaf_path();
+
+            // Fields named like a context field bind that field rather than one of the leaf's.
+            let context_names = nested.context_names();
+            let mut context = vec![None; context_names.len()];
+            let fields = std::mem::take(&mut pat_struct.fields);
+            for pair in fields.into_pairs() {
+                let (field, punct) = pair.into_tuple();
+                let position = match &field.member {
+                    syn::Member::Named(ident) => {
+                        context_names.iter().position(|name| *name == Some(ident))
+                    }
+                    syn::Member::Unnamed(_) => None,
+                };
+                match position {
+                    Some(position) => context[position] = Some(*field.pat),
+                    None => pat_struct
+                        .fields
+                        .extend(std::iter::once(syn::punctuated::Pair::new(field, punct))),
+                }
+            }
+            let context = context
+                .into_iter()
+                .map(|pat| pat.unwrap_or_else(|| syn::parse_quote!(_)))
+                .collect();
+
+            let leaf = match nested.leaf_fields {
+                Fields::Unit if pat_struct.fields.is_empty() => Pat::Path(syn::PatPath {
+                    attrs: pat_struct.attrs,
+                    qself: pat_struct.qself,
+                    path: pat_struct.path,
+                }),
+                _ => Pat::Struct(pat_struct),
+            };
+            Ok(nested.wrap(leaf, context, pointers))
+        }
+        Pat::Or(mut pat_or) => {
+            // A pointer bound in one alternative would not be bound by the others.
+            let span = pat_or.span();
+            let mut inner = PointerMatches::default();
+            pat_or.cases = rewrite_pats(pat_or.cases, resolver, &mut inner)?;
+            if !inner.is_empty() {
+                return Err(syn::Error::new(
+                    span,
+                    "or-patterns through Box, Rc or Arc are only supported at the top of a pattern",
+                ));
+            }
+            Ok(Pat::Or(pat_or))
+        }
+        Pat::Tuple(mut pat_tuple) => {
+            pat_tuple.elems = rewrite_pats(pat_tuple.elems, resolver, pointers)?;
+            Ok(Pat::Tuple(pat_tuple))
+        }
+        Pat::Slice(mut pat_slice) => {
+            pat_slice.elems = rewrite_pats(pat_slice.elems, resolver, pointers)?;
+            Ok(Pat::Slice(pat_slice))
+        }
+        Pat::Reference(mut pat_ref) => {
+            rewrite_pat_in_place(&mut pat_ref.pat, resolver, pointers)?;
+            Ok(Pat::Reference(pat_ref))
+        }
+        Pat::Paren(mut pat_paren) => {
+            rewrite_pat_in_place(&mut pat_paren.pat, resolver, pointers)?;
+            Ok(Pat::Paren(pat_paren))
+        }
+        Pat::Type(mut pat_type) => {
+            rewrite_pat_in_place(&mut pat_type.pat, resolver, pointers)?;
+            Ok(Pat::Type(pat_type))
+        }
+        Pat::Ident(mut pat_ident) => {
+            if let Some((_, subpat)) = &mut pat_ident.subpat {
+                rewrite_pat_in_place(subpat, resolver, pointers)?;
+            }
+            Ok(Pat::Ident(pat_ident))
+        }
+        other => Ok(other),
+    }
 }
 
-fn rewrite_pat_struct(
-    pat_struct: PatStruct,
-    current_file: &str,
-    module_root: &std::path::Path,
-    current_module: &str,
-    enums_by_ident: &HashMap<String, ItemEnum>,
-    cache: &mut HashMap<String, HashMap<String, ItemEnum>>,
-) -> Result<Pat, syn::Error> {
-    let Some((module_path, explicit_crate, outer_enum, outer_variant, inner_variant)) =
-        split_nested_path(&pat_struct.path)?
-    else {
-        if let Some(new_path) = rewrite_outer_variant_path(
-            &pat_struct.path,
-            current_file,
-            module_root,
-            current_module,
-            enums_by_ident,
-            cache,
-        )? {
-            return Ok(Pat::Struct(PatStruct {
-                attrs: pat_struct.attrs,
-                qself: pat_struct.qself,
-                path: new_path,
-                brace_token: pat_struct.brace_token,
-                fields: pat_struct.fields,
-                rest: pat_struct.rest,
-            }));
-        }
-        return Ok(Pat::Struct(pat_struct));
-    };
+fn rewrite_pats<P>(
+    pats: Punctuated<Pat, P>,
+    resolver: &mut Resolver,
+    pointers: &mut PointerMatches,
+) -> Result<Punctuated<Pat, P>, syn::Error> {
+    pats.into_pairs()
+        .map(|pair| {
+            let (pat, punct) = pair.into_tuple();
+            Ok(syn::punctuated::Pair::new(
+                rewrite_pat(pat, resolver, pointers)?,
+                punct,
+            ))
+        })
+        .collect()
+}
 
-    let Some(outer_info) = resolve_enum_from_path(
-        &module_path,
-        explicit_crate,
-        &outer_enum,
-        current_file,
-        module_root,
-        current_module,
-        enums_by_ident,
-        cache,
-    )?
-    else {
-        return Ok(Pat::Struct(pat_struct));
+/// Rewrites a struct literal with a nested path, e.g. `Outer::Wrap::Struct { x: 5 }` becomes
+/// `Outer::Outer::Wrap(Inner::Inner::Struct { x: 5 })`.
+///
+/// A `..base` update is rejected: the base is a value of the outer enum, which may hold another
+/// variant, so its fields could only be read by a match that fails at runtime.
+fn rewrite_struct_expr(
+    expr: &syn::ExprStruct,
+    resolver: &mut Resolver,
+) -> Result<Option<syn::Expr>, syn::Error> {
+    if expr.qself.is_some() {
+        return Ok(None);
+    }
+    let Some(nested) = resolve_nested_pat_path(&expr.path, resolver)? else {
+        return Ok(None);
     };
 
-    let (outer_enum_item, outer_marked) = outer_info;
-    if !outer_marked {
+    let path = &expr.path;
+    let path_str = quote!(#path).to_string().replace(' ', "");
+    if let Some(base) = &expr.rest {
         return Err(syn::Error::new(
-            pat_struct.span(),
+            base.span(),
             format!(
-                "enum {} is not marked with #[nestum]; \
-only #[nestum] enums support nested match patterns",
-                outer_enum
+                "struct update syntax is not supported with nested paths, since the base may \
+hold another variant than {path_str}; destructure the base first, e.g. \
+`let {path_str} {{ y, .. }} = base else {{ ... }};`, and name every field"
             ),
         ));
     }
 
-    let Some((inner_enum_ident, inner_enum_path, inner_explicit_crate)) =
-        resolve_inner_enum_path(&outer_enum_item, &outer_variant, enums_by_ident)?
-    else {
-        return Ok(Pat::Struct(pat_struct));
+    let attrs = &expr.attrs;
+    let leaf_path = nested.leaf_path();
+    let context = nested.context_names();
+    let explicit: Vec<&syn::Member> = expr.fields.iter().map(|field| &field.member).collect();
+    let is_context = |member: &syn::Member| match member {
+        syn::Member::Named(ident) => context.contains(&Some(ident)),
+        syn::Member::Unnamed(_) => false,
     };
+    let explicit_leaf: Vec<&syn::Member> = explicit
+        .iter()
+        .copied()
+        .filter(|member| !is_context(member))
+        .collect();
 
-    let inner_enum_info = resolve_enum_from_path(
-        &inner_enum_path,
-        inner_explicit_crate,
-        &inner_enum_ident,
-        current_file,
-        module_root,
-        current_module,
-        enums_by_ident,
-        cache,
-    )?;
-
-    let (inner_enum_item, inner_marked) = inner_enum_info.ok_or_else(|| {
-        syn::Error::new(
-            pat_struct.span(),
-            format!(
-                "inner enum {} not found for {}::{}; \
-ensure it is declared in the referenced module",
-                inner_enum_ident, outer_enum, outer_variant
-            ),
-        )
-    })?;
-    if !inner_marked {
+    let members: Vec<syn::Member> = nested
+        .leaf_fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| match &field.ident {
+            Some(ident) => syn::Member::Named(ident.clone()),
+            None => syn::Member::Unnamed(syn::Index::from(index)),
+        })
+        .collect();
+    if let Some(unknown) = explicit_leaf
+        .iter()
+        .find(|member| !members.contains(member))
+    {
         return Err(syn::Error::new(
-            pat_struct.span(),
+            unknown.span(),
             format!(
-                "inner enum {} is not marked with #[nestum]; \
-only #[nestum] enums support nested match patterns",
-                inner_enum_ident
+                "no field {} on variant {}::{}",
+                quote!(#unknown),
+                nested
+                    .leaf_enum
+                    .segments
+                    .last()
+                    .map(|segment| segment.ident.to_string())
+                    .unwrap_or_default(),
+                nested.leaf_variant
             ),
         ));
     }
 
-    ensure_inner_variant_exists(&inner_enum_item, &inner_variant)?;
+    if context.is_empty() {
+        let fields = &expr.fields;
+        return Ok(Some(nested.wrap_expr(
+            syn::parse_quote!(#(#attrs)* #leaf_path { #fields }),
+            Vec::new(),
+        )));
+    }
 
-    let outer_module_idents = effective_module_idents(&module_path, explicit_crate, current_module);
-    let inner_module_idents = if inner_enum_path.is_empty() {
-        outer_module_idents.clone()
-    } else {
-        effective_module_idents(&inner_enum_path, inner_explicit_crate, current_module)
+    // Every explicit value is bound first, in source order, since each may land in a different
+    // level of the nested value.
+    let binding = |member: &syn::Member| match member {
+        syn::Member::Named(ident) => format_ident!("__nestum_{}", ident),
+        syn::Member::Unnamed(index) => format_ident!("__nestum_{}", index.index),
     };
-    let outer_variant_path = build_path_from_idents(
-        outer_module_idents,
-        &[
-            outer_enum.clone(),
-            outer_enum.clone(),
-            outer_variant.clone(),
-        ],
-    );
-    let inner_variant_path = build_path_from_idents(
-        inner_module_idents,
-        &[
-            inner_enum_ident.clone(),
-            inner_enum_ident.clone(),
-            inner_variant,
-        ],
-    );
-    let fields = pat_struct.fields;
-    let rest = pat_struct.rest;
-
-    let inner_pat = Pat::Struct(PatStruct {
-        attrs: Vec::new(),
-        qself: None,
-        path: inner_variant_path,
-        brace_token: pat_struct.brace_token,
-        fields,
-        rest,
-    });
+    let explicit_bindings: Vec<_> = explicit.iter().map(|member| binding(member)).collect();
+    let explicit_values = expr.fields.iter().map(|field| &field.expr);
+    let explicit_leaf_bindings: Vec<_> =
+        explicit_leaf.iter().map(|member| binding(member)).collect();
+
+    let mut context_values = Vec::new();
+    for (index, name) in context.iter().enumerate() {
+        if let Some(name) = name
+            && explicit.contains(&&syn::Member::Named((*name).clone()))
+        {
+            let ident = format_ident!("__nestum_{}", name);
+            context_values.push(syn::parse_quote!(#ident));
+        } else {
+            let name = match name {
+                Some(name) => name.to_string(),
+                None => format!("#{index}"),
+            };
+            return Err(syn::Error::new(
+                expr.path.span(),
+                format!(
+                    "missing context field {name} for {path_str}; \
+name it in the literal, or use the constructor function for tuple context fields"
+                ),
+            ));
+        }
+    }
 
-    Ok(Pat::TupleStruct(PatTupleStruct {
-        attrs: Vec::new(),
-        qself: None,
-        path: outer_variant_path,
-        paren_token: Default::default(),
-        elems: Punctuated::from_iter(std::iter::once(inner_pat)),
+    let construct = nested.wrap_expr(
+        syn::parse_quote! {
+            #(#attrs)* #leaf_path { #(#explicit_leaf: #explicit_leaf_bindings,)* }
+        },
+        context_values,
+    );
+    Ok(Some(syn::parse_quote! {
+        {
+            #(let #explicit_bindings = #explicit_values;)*
+            #construct
+        }
     }))
 }
 
-fn split_nested_path(
-    path: &syn::Path,
-) -> Result<Option<(Vec<syn::Ident>, bool, syn::Ident, syn::Ident, syn::Ident)>, syn::Error> {
-    let segments: Vec<_> = path.segments.iter().map(|s| s.ident.clone()).collect();
-    if segments.len() < 3 {
-        return Ok(None);
+/// A pattern path resolved to a `#[nestum]` enum and the chain of variants below it, e.g.
+/// `Event::Documents::Lifecycle::Archived` starts at `Event` and descends through `Documents`
+/// and `Lifecycle` to the `Archived` leaf. The leaf may itself be a nested variant, as in
+/// `Event::Documents::Lifecycle(inner)`, which binds the whole inner `LifecycleEvent`.
+struct NestedPatPath {
+    /// Every level above the leaf, outermost first.
+    wrappers: Vec<NestedLevel>,
+    /// Absolute path of the enum that declares the leaf variant.
+    leaf_enum: syn::Path,
+    leaf_variant: syn::Ident,
+    leaf_fields: Fields,
+    /// Whether the leaf is itself a nested variant, so the pattern covers a whole subtree.
+    leaf_is_subtree: bool,
+}
+
+impl NestedPatPath {
+    fn leaf_path(&self) -> syn::Path {
+        let mut path = self.leaf_enum.clone();
+        path.segments.push(self.leaf_variant.clone().into());
+        path
     }
 
-    let outer_idx = segments.len() - 3;
-    let variant_idx = segments.len() - 2;
-    let inner_idx = segments.len() - 1;
-    let module_path = segments[..outer_idx].to_vec();
-    let explicit_crate = module_path
-        .first()
-        .map(|ident| ident == "crate")
-        .unwrap_or(false);
-    Ok(Some((
-        module_path,
-        explicit_crate,
-        segments[outer_idx].clone(),
-        segments[variant_idx].clone(),
-        segments[inner_idx].clone(),
-    )))
-}
+    /// Names of the context fields of every level, outermost first (`None` for tuple fields).
+    fn context_names(&self) -> Vec<Option<&syn::Ident>> {
+        self.wrappers
+            .iter()
+            .flat_map(|level| context_fields(&level.fields, level.index))
+            .map(|field| field.ident.as_ref())
+            .collect()
+    }
 
-fn split_outer_variant_path(
-    path: &syn::Path,
-) -> Option<(Vec<syn::Ident>, bool, syn::Ident, syn::Ident)> {
-    let segments: Vec<_> = path.segments.iter().map(|s| s.ident.clone()).collect();
-    if segments.len() < 2 {
-        return None;
+    /// Splits the context patterns off the front of a tuple-struct pattern on the leaf, e.g.
+    /// `Event::Documents::Update(tenant, doc)`, mirroring the constructor's arguments. Without
+    /// them, the context fields match anything.
+    fn split_context_pats(&self, pat: &mut PatTupleStruct) -> Result<Vec<Pat>, syn::Error> {
+        let context_len = self.context_names().len();
+        if context_len == 0 {
+            return Ok(Vec::new());
+        }
+        let leaf_len = self.leaf_fields.len();
+
+        let wildcard = || -> Pat { syn::parse_quote!(_) };
+        let mut elems: Vec<Pat> = std::mem::take(&mut pat.elems).into_iter().collect();
+        let total = context_len + leaf_len;
+        if let Some(rest) = elems.iter().position(|elem| matches!(elem, Pat::Rest(_))) {
+            let after = elems.split_off(rest + 1);
+            elems.pop();
+            if elems.len() + after.len() <= total {
+                let fill = total - elems.len() - after.len();
+                elems.extend(std::iter::repeat_with(wildcard).take(fill));
+                elems.extend(after);
+            }
+        } else if elems.len() == leaf_len {
+            let mut all: Vec<Pat> = std::iter::repeat_with(wildcard).take(context_len).collect();
+            all.append(&mut elems);
+            elems = all;
+        }
+        if elems.len() != total {
+            let path = &pat.path;
+            return Err(syn::Error::new(
+                pat.span(),
+                format!(
+                    "{} takes {context_len} context field(s) followed by {leaf_len} field(s); \
+pass all of them or only the variant's own fields",
+                    quote!(#path).to_string().replace(' ', "")
+                ),
+            ));
+        }
+
+        let leaf = elems.split_off(context_len);
+        pat.elems = leaf.into_iter().collect();
+        Ok(elems)
     }
 
-    let outer_idx = segments.len() - 2;
-    let variant_idx = segments.len() - 1;
-    let module_path = segments[..outer_idx].to_vec();
-    let explicit_crate = module_path
-        .first()
-        .map(|ident| ident == "crate")
-        .unwrap_or(false);
-    Some((
-        module_path,
-        explicit_crate,
-        segments[outer_idx].clone(),
-        segments[variant_idx].clone(),
-    ))
+    /// Wraps the leaf expression in one variant constructor call per enclosing level, filling in
+    /// the context fields from `context` (outermost first).
+    fn wrap_expr(&self, leaf: syn::Expr, context: Vec<syn::Expr>) -> syn::Expr {
+        let context = self.split_context(context.into_iter().map(|expr| quote!(#expr)));
+        self.wrappers
+            .iter()
+            .zip(context)
+            .rev()
+            .fold(leaf, |inner, (level, context)| {
+                let path = &level.path;
+                let inner = match &level.pointer {
+                    Some((pointer, _)) => pointer.wrap_expr(quote!(#inner)),
+                    None => quote!(#inner),
+                };
+                let expr = nested_variant_tokens(
+                    quote!(#path),
+                    &level.fields,
+                    level.index,
+                    context,
+                    inner,
+                );
+                syn::parse_quote!(#expr)
+            })
+    }
+
+    /// Wraps the leaf pattern in one variant pattern per enclosing level, with the context
+    /// fields matching `context` (outermost first). Levels behind a pointer bind it instead,
+    /// recording the inner pattern in `pointers`.
+    fn wrap(&self, leaf: Pat, context: Vec<Pat>, pointers: &mut PointerMatches) -> Pat {
+        let context = self.split_context(context.into_iter().map(|pat| quote!(#pat)));
+        self.wrappers
+            .iter()
+            .zip(context)
+            .rev()
+            .fold(leaf, |inner, (level, context)| {
+                let inner = match &level.pointer {
+                    Some((_, target)) => pointers.bind(target.clone(), inner),
+                    None => inner,
+                };
+                let path = &level.path;
+                let pat = nested_variant_tokens(
+                    quote!(#path),
+                    &level.fields,
+                    level.index,
+                    context,
+                    quote!(#inner),
+                );
+                syn::parse_quote!(#pat)
+            })
+    }
+
+    /// Distributes flattened context values over the levels; missing ones become `_`.
+    fn split_context(
+        &self,
+        context: impl Iterator<Item = proc_macro2::TokenStream>,
+    ) -> Vec<Vec<proc_macro2::TokenStream>> {
+        let mut context = context.fuse();
+        self.wrappers
+            .iter()
+            .map(|level| {
+                context_fields(&level.fields, level.index)
+                    .iter()
+                    .map(|_| context.next().unwrap_or_else(|| quote!(_)))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// One nested variant on the way down to a leaf.
+struct NestedLevel {
+    /// Absolute variant path, e.g. `::crate::Event::Event::Documents`.
+    path: syn::Path,
+    /// The pointer around the inner enum, with the reference type it derefs to.
+    pointer: Option<(Pointer, syn::Type)>,
+    /// The variant's fields; the one at `index` holds the inner enum, the rest are context.
+    fields: Fields,
+    index: usize,
 }
 
-fn rewrite_outer_variant_path(
+/// Splits `path` into an optional module prefix, a `#[nestum]` enum, and one or more variants,
+/// then walks the variants through each nested enum.
+///
+/// Returns `None` when the path does not start at a marked enum (plain enum paths, shadow module
+/// paths like `Outer::Outer::Variant`, and anything else are left to the compiler).
+fn resolve_nested_pat_path(
     path: &syn::Path,
-    current_file: &str,
-    module_root: &std::path::Path,
-    current_module: &str,
-    enums_by_ident: &HashMap<String, ItemEnum>,
-    cache: &mut HashMap<String, HashMap<String, ItemEnum>>,
-) -> Result<Option<syn::Path>, syn::Error> {
-    let Some((module_path, explicit_crate, outer_enum, outer_variant)) =
-        split_outer_variant_path(path)
-    else {
+    resolver: &mut Resolver,
+) -> Result<Option<NestedPatPath>, syn::Error> {
+    let segments: Vec<_> = path.segments.iter().map(|s| s.ident.clone()).collect();
+    if path.leading_colon.is_some() || segments.len() < 2 {
         return Ok(None);
-    };
+    }
+
+    let mut start = None;
+    for enum_idx in 0..segments.len() - 1 {
+        let module_path = &segments[..enum_idx];
+        let explicit_crate = module_path
+            .first()
+            .map(|ident| ident == "crate")
+            .unwrap_or(false);
+        let Ok(Some(resolved)) =
+            resolver.resolve_enum_from_path(module_path, explicit_crate, &segments[enum_idx])
+        else {
+            continue;
+        };
+
+        let variant = &segments[enum_idx + 1];
+        let is_last = enum_idx + 2 == segments.len();
+        if !resolved.item.variants.iter().any(|v| v.ident == *variant) {
+            if is_last && resolved.marked {
+                return Err(syn::Error::new(
+                    path.span(),
+                    format!(
+                        "variant {} not found on enum {}",
+                        variant, resolved.item.ident
+                    ),
+                ));
+            }
+            continue;
+        }
+
+        if !resolved.marked {
+            if is_last {
+                return Ok(None);
+            }
+            return Err(syn::Error::new(
+                path.span(),
+                format!(
+                    "enum {} is not marked with #[nestum]; \
+only #[nestum] enums support nested match patterns",
+                    resolved.item.ident
+                ),
+            ));
+        }
 
-    let Some(outer_info) = resolve_enum_from_path(
-        &module_path,
-        explicit_crate,
-        &outer_enum,
-        current_file,
-        module_root,
-        current_module,
-        enums_by_ident,
-        cache,
-    )?
-    else {
+        start = Some((enum_idx, resolved));
+        break;
+    }
+
+    let Some((enum_idx, root)) = start else {
         return Ok(None);
     };
 
-    let (outer_enum_item, outer_marked) = outer_info;
-    if !outer_marked {
-        return Ok(None);
+    let variants = &segments[enum_idx + 1..];
+    let mut wrappers = Vec::new();
+    let mut current = root.item;
+    let mut current_module = root.module_path;
+    for variant in &variants[..variants.len() - 1] {
+        let variant_item = current
+            .variants
+            .iter()
+            .find(|v| v.ident == *variant)
+            .cloned()
+            .ok_or_else(|| {
+                syn::Error::new(
+                    variant.span(),
+                    format!(
+                        "variant {} not found on inner enum {}",
+                        variant, current.ident
+                    ),
+                )
+            })?;
+        let options = EnumOptions::from_attrs(&current)?;
+        let child = resolver
+            .nested_child(&current, &options, &current_module, &variant_item)?
+            .ok_or_else(|| {
+                syn::Error::new(
+                    path.span(),
+                    format!(
+                        "variant {}::{} does not nest a #[nestum] enum; \
+nested paths can only descend through nested variants",
+                        current.ident, variant
+                    ),
+                )
+            })?;
+
+        let mut path = enum_type_path(&current_module, &current);
+        path.segments.push(variant.clone().into());
+        let pointer = child.pointer.clone().map(|pointer| {
+            let target = inferred_type(child.enum_path(), &child.enum_item.generics);
+            (pointer, syn::parse_quote!(&#target))
+        });
+        wrappers.push(NestedLevel {
+            path,
+            pointer,
+            fields: child.fields.clone(),
+            index: child.index,
+        });
+
+        current = child.enum_item;
+        current_module = child.module_path;
     }
 
-    if !outer_enum_item
+    let leaf_variant = variants[variants.len() - 1].clone();
+    ensure_inner_variant_exists(&current, &leaf_variant)?;
+    let leaf_item = current
         .variants
         .iter()
-        .any(|v| v.ident == outer_variant)
-    {
-        return Err(syn::Error::new(
-            path.span(),
-            format!("variant {} not found on enum {}", outer_variant, outer_enum),
-        ));
+        .find(|v| v.ident == leaf_variant)
+        .cloned()
+        .expect("leaf variant checked above");
+    let leaf_is_subtree = resolver
+        .nested_child(
+            &current,
+            &EnumOptions::from_attrs(&current)?,
+            &current_module,
+            &leaf_item,
+        )?
+        .is_some();
+
+    Ok(Some(NestedPatPath {
+        wrappers,
+        leaf_enum: enum_type_path(&current_module, &current),
+        leaf_variant,
+        leaf_fields: leaf_item.fields,
+        leaf_is_subtree,
+    }))
+}
+
+/// `path` applied to inferred arguments for `generics`, e.g. `Inner<'_, _>`.
+fn inferred_type(mut path: syn::Path, generics: &syn::Generics) -> syn::Type {
+    if !generics.params.is_empty() {
+        let args = generics.params.iter().map(|param| match param {
+            syn::GenericParam::Lifetime(_) => quote!('_),
+            _ => quote!(_),
+        });
+        if let Some(last) = path.segments.last_mut() {
+            last.arguments = syn::PathArguments::AngleBracketed(syn::parse_quote!(<#(#args),*>));
+        }
     }
+    syn::parse_quote!(#path)
+}
 
-    let outer_module_idents = effective_module_idents(&module_path, explicit_crate, current_module);
-    let outer_variant_path = build_path_from_idents(
-        outer_module_idents,
-        &[
-            outer_enum.clone(),
-            outer_enum.clone(),
-            outer_variant.clone(),
-        ],
-    );
-    Ok(Some(outer_variant_path))
+/// An enum found in source, with the module that declares it.
+struct ResolvedEnum {
+    item: ItemEnum,
+    module_path: String,
+    marked: bool,
 }
 
-fn resolve_enum_from_path(
-    module_path: &[syn::Ident],
-    explicit_crate: bool,
-    enum_ident: &syn::Ident,
-    current_file: &str,
-    module_root: &std::path::Path,
-    current_module: &str,
-    enums_by_ident: &HashMap<String, ItemEnum>,
-    cache: &mut HashMap<String, HashMap<String, ItemEnum>>,
-) -> Result<Option<(ItemEnum, bool)>, syn::Error> {
-    let module_path_str = if module_path.is_empty() {
-        current_module.to_string()
-    } else {
-        let mut segments = module_path
-            .iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>();
-        let is_crate = segments.first().map(|s| s.as_str()) == Some("crate");
-        if is_crate {
-            segments.remove(0);
-        }
-        let base = if segments.is_empty() {
-            "crate".to_string()
-        } else {
-            segments.join("::")
-        };
-        if is_crate || current_module == "crate" || explicit_crate {
-            base
-        } else {
-            format!("{current_module}::{base}")
+/// Enums read from source for one macro invocation, keyed by module path then enum ident.
+struct Resolver {
+    current_file: String,
+    module_root: std::path::PathBuf,
+    current_module: String,
+    cache: HashMap<String, HashMap<String, ItemEnum>>,
+    /// Module paths that could not be located, so repeated lookups (common when rewriting whole
+    /// function bodies) do not rescan the source tree.
+    missing: HashSet<String>,
+    /// Inline modules that pull in items with `include!`, which cannot be read from source.
+    included: HashSet<String>,
+    /// Line of the macro call in `current_file`, passed on when the call is re-run.
+    current_line: usize,
+    /// Descriptors of enums exported by other crates, as received by `__nestum_expand!`.
+    descriptors: Vec<Descriptor>,
+    /// External paths naming another crate's enum, mapped to the module key its descriptor
+    /// declares it in.
+    extern_roots: HashMap<String, String>,
+    /// The first external path naming another crate's enum whose descriptor is still needed.
+    pending: Option<syn::Path>,
+}
+
+impl Resolver {
+    fn new(site: CallSite<'_>) -> Result<Self, syn::Error> {
+        let (file_path, line) = source_info(site)?;
+        Resolver::at(file_path, line)
+    }
+
+    fn at(current_file: String, current_line: usize) -> Result<Self, syn::Error> {
+        let (module_root, current_module) = module_context_at(&current_file, current_line)?;
+        let (cache, included) =
+            collect_enums_by_module_path(&current_file, &module_root, &current_file)?;
+        Ok(Resolver {
+            current_file,
+            module_root,
+            current_module,
+            cache,
+            missing: HashSet::new(),
+            included,
+            current_line,
+            descriptors: Vec::new(),
+            extern_roots: HashMap::new(),
+            pending: None,
+        })
+    }
+
+    /// Makes the enums of another crate's descriptor available under `::<crate>::<module>` keys.
+    fn add_descriptor(&mut self, descriptor: Descriptor) {
+        let krate = &descriptor.path.segments[0].ident;
+        for (index, (module, item)) in descriptor.enums.iter().enumerate() {
+            let module = match module.as_str() {
+                "crate" => format!("::{krate}"),
+                module if module.starts_with("::") => module.to_string(),
+                module => format!("::{krate}::{module}"),
+            };
+            if index == 0 {
+                self.extern_roots
+                    .insert(external_path_to_string(&descriptor.path), module.clone());
+            }
+            self.cache
+                .entry(module)
+                .or_default()
+                .insert(item.ident.to_string(), item.clone());
         }
-    };
+        self.descriptors.push(descriptor);
+    }
 
-    if module_path_str == current_module {
-        let item = enums_by_ident.get(&enum_ident.to_string()).cloned();
-        let marked = item
+    /// When resolution stopped at an enum exported by another crate, a call to that crate's
+    /// descriptor macro which passes the descriptor, along with `input`, back to
+    /// `__nestum_expand!` to run the expansion again.
+    fn descriptor_request(
+        &self,
+        input: proc_macro2::TokenStream,
+    ) -> Option<proc_macro2::TokenStream> {
+        let path = self
+            .pending
             .as_ref()
-            .map(|i| matches!(nestum_attr_kind(&i.attrs), Ok(NestumAttrKind::Empty)))
-            .unwrap_or(false);
-        return Ok(item.map(|i| (i, marked)));
+            .filter(|path| !self.descriptors.iter().any(|seen| seen.path == **path))?;
+        let krate = &path.segments.first()?.ident;
+        let name = descriptor_macro_ident(&path.segments.last()?.ident);
+        let file = &self.current_file;
+        let line = self.current_line;
+        let descriptors = &self.descriptors;
+        Some(quote! {
+            #krate::#name! {
+                { ::nestum::__nestum_expand }
+                { source = (#file, #line) #input #(#descriptors)* descriptor #path }
+            }
+        })
     }
 
-    if let Err(err) = load_module_enums(
-        proc_macro2::Span::call_site(),
-        &module_path_str,
-        current_file,
-        module_root,
-        cache,
-    ) {
-        if explicit_crate {
-            return Err(err);
+    /// Enums declared in the module of the macro call.
+    fn current_enums(&self) -> Option<&HashMap<String, ItemEnum>> {
+        self.cache.get(&self.current_module)
+    }
+
+    fn load_module_enums(
+        &mut self,
+        span: proc_macro2::Span,
+        module_path: &str,
+    ) -> Result<(), syn::Error> {
+        if self.cache.contains_key(module_path) {
+            return Ok(());
         }
-        return Ok(None);
+        if self.missing.contains(module_path) {
+            return Err(self.missing_module_error(span, module_path));
+        }
+
+        let module_file = module_path_extractor::module_path_to_file(
+            module_path,
+            &self.current_file,
+            &self.module_root,
+        );
+        let module_file = match module_file {
+            Some(file) => file,
+            None => {
+                let (all, included) = collect_enums_by_module_path(
+                    &self.current_file,
+                    &self.module_root,
+                    &self.current_file,
+                )?;
+                if all.contains_key(module_path) {
+                    self.cache.extend(all);
+                    return Ok(());
+                }
+                self.included.extend(included);
+                self.missing.insert(module_path.to_string());
+                return Err(self.missing_module_error(span, module_path));
+            }
+        };
+
+        let (all, included) = collect_enums_by_module_path(
+            module_file.to_string_lossy().as_ref(),
+            &self.module_root,
+            &self.current_file,
+        )?;
+        self.cache.extend(all);
+        self.included.extend(included);
+
+        Ok(())
     }
 
-    let enums = match cache.get(&module_path_str) {
-        Some(enums) => enums,
-        None => return Ok(None),
-    };
+    fn missing_module_error(&self, span: proc_macro2::Span, module_path: &str) -> syn::Error {
+        let message = if self.included.contains(module_path) {
+            format!(
+                "module {module_path} takes its items from include!(), which nestum cannot read; \
+declare the enums in the module itself or move them to a module file"
+            )
+        } else {
+            format!(
+                "unable to locate module file for {module_path}; \
+expected {module_path}.rs or {module_path}/mod.rs under the module root"
+            )
+        };
+        syn::Error::new(span, message)
+    }
 
-    let item = enums.get(&enum_ident.to_string()).cloned();
-    let marked = item
-        .as_ref()
-        .map(|i| matches!(nestum_attr_kind(&i.attrs), Ok(NestumAttrKind::Empty)))
-        .unwrap_or(false);
-    Ok(item.map(|i| (i, marked)))
-}
+    /// Loads the enums declared in `module_path` along with the set of idents marked
+    /// `#[nestum]`.
+    fn module_enums(
+        &mut self,
+        span: proc_macro2::Span,
+        module_path: &str,
+    ) -> Result<(HashMap<String, ItemEnum>, HashSet<String>), syn::Error> {
+        self.load_module_enums(span, module_path)?;
+        let enums = self.cache.get(module_path).cloned().unwrap_or_default();
+        let mut marked = HashSet::new();
+        for (name, info) in enums.iter() {
+            if has_nestum_attr(&info.attrs) {
+                marked.insert(name.clone());
+            }
+        }
+        Ok((enums, marked))
+    }
 
-fn resolve_inner_enum_path(
-    outer_enum: &ItemEnum,
-    outer_variant: &syn::Ident,
-    enums_by_ident: &HashMap<String, ItemEnum>,
-) -> Result<Option<(syn::Ident, Vec<syn::Ident>, bool)>, syn::Error> {
-    let Some(variant) = outer_enum
-        .variants
-        .iter()
-        .find(|v| v.ident == *outer_variant)
-    else {
-        return Ok(None);
-    };
+    /// Looks up `enum_ident` in `module_path`, which is relative to the current module unless
+    /// it starts with `crate`. Leading `self` and `super` segments are resolved.
+    fn resolve_enum_from_path(
+        &mut self,
+        module_path: &[syn::Ident],
+        explicit_crate: bool,
+        enum_ident: &syn::Ident,
+    ) -> Result<Option<ResolvedEnum>, syn::Error> {
+        let module_path_str = if module_path.is_empty() {
+            self.current_module.clone()
+        } else {
+            let mut segments = module_path
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>();
+            let is_crate = segments.first().map(|s| s.as_str()) == Some("crate");
+            if is_crate {
+                segments.remove(0);
+            }
+            let mut full = if is_crate || self.current_module == "crate" || explicit_crate {
+                Vec::new()
+            } else {
+                self.current_module
+                    .split("::")
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            };
+            // Leading `self` and `super` are relative to the current module.
+            while let Some(first) = segments.first() {
+                match first.as_str() {
+                    "self" => {}
+                    "super" if full.pop().is_some() => {}
+                    "super" => return Ok(None),
+                    _ => break,
+                }
+                segments.remove(0);
+            }
+            full.extend(segments);
+            if full.is_empty() {
+                "crate".to_string()
+            } else {
+                full.join("::")
+            }
+        };
 
-    let external_path = parse_variant_external_path(&variant.attrs)?;
-    if let Some(path) = external_path {
-        let explicit_crate = path
-            .segments
-            .first()
-            .map(|s| s.ident == "crate")
-            .unwrap_or(false);
-        let (module_path, enum_ident) = split_module_and_ident(&path).ok_or_else(|| {
+        if let Err(err) = self.load_module_enums(proc_macro2::Span::call_site(), &module_path_str) {
+            if explicit_crate {
+                return Err(err);
+            }
+            return Ok(None);
+        }
+
+        let Some(enums) = self.cache.get(&module_path_str) else {
+            return Ok(None);
+        };
+        // Companion modules stand in for the enum in nested paths, e.g. `event::Documents`.
+        let item = enums.get(&enum_ident.to_string()).or_else(|| {
+            enums.values().find(|item| {
+                has_nestum_attr(&item.attrs)
+                    && EnumOptions::from_attrs(item)
+                        .is_ok_and(|options| options.companion.as_ref() == Some(enum_ident))
+            })
+        });
+        let Some(item) = item.cloned() else {
+            return Ok(None);
+        };
+        let marked = has_nestum_attr(&item.attrs);
+        Ok(Some(ResolvedEnum {
+            item,
+            module_path: module_path_str,
+            marked,
+        }))
+    }
+
+    /// Looks up the enum named by `#[nestum(external = "...")]` on a variant of an enum declared
+    /// in `from_module`, returning it with the module key it was found under.
+    ///
+    /// Paths that do not start with `crate` and name no local module are taken to start with a
+    /// crate name; their enum comes from that crate's descriptor, which is requested through
+    /// `pending` if it has not been received yet.
+    fn resolve_external_enum(
+        &mut self,
+        path: &syn::Path,
+        from_module: &str,
+    ) -> Result<Option<(ItemEnum, bool, String)>, syn::Error> {
+        let (mut module_path, enum_ident) = split_module_and_ident(path).ok_or_else(|| {
             syn::Error::new(
                 path.span(),
                 "external path must include an enum ident, e.g. crate::foo::Enum",
             )
         })?;
-        let module_idents = module_path
-            .split("::")
-            .filter(|s| !s.is_empty())
-            .map(|s| syn::Ident::new(s, proc_macro2::Span::call_site()))
-            .collect();
-        return Ok(Some((
-            syn::Ident::new(&enum_ident, proc_macro2::Span::call_site()),
-            module_idents,
-            explicit_crate,
-        )));
-    }
+        let in_crate = path.segments.len() == 1 || path.segments[0].ident == "crate";
+
+        // Enums from a descriptor spell paths relative to the crate that exported them.
+        let extern_crate = from_module
+            .starts_with("::")
+            .then(|| from_module.split("::").nth(1).unwrap_or_default());
+        if let Some(krate) = extern_crate {
+            let prefixed = match module_path.as_str() {
+                "crate" => format!("::{krate}"),
+                module => format!("::{krate}::{module}"),
+            };
+            if in_crate || self.cache.contains_key(&prefixed) {
+                module_path = prefixed;
+            }
+        }
+        if let Some(root) = self.extern_roots.get(&external_path_to_string(path)) {
+            module_path = root.clone();
+        }
+
+        if !module_path.starts_with("::") {
+            if let Err(err) = self.load_module_enums(path.span(), &module_path) {
+                if in_crate {
+                    return Err(err);
+                }
+                self.pending.get_or_insert_with(|| path.clone());
+                return Ok(None);
+            }
+        } else if !self.cache.contains_key(&module_path) {
+            if !in_crate {
+                self.pending.get_or_insert_with(|| path.clone());
+            }
+            return Ok(None);
+        }
+
+        let enums = match self.cache.get(&module_path) {
+            Some(enums) => enums,
+            None => return Ok(None),
+        };
+
+        let item_enum = match enums.get(&enum_ident) {
+            Some(item_enum) => item_enum.clone(),
+            None => return Ok(None),
+        };
+
+        let marked = has_nestum_attr(&item_enum.attrs);
+        Ok(Some((item_enum, marked, module_path)))
+    }
+
+    /// Resolves the enum nested in `variant`, if any.
+    ///
+    /// Variants with `#[nestum(external = "...")]` must resolve to a marked enum; otherwise a
+    /// single-field tuple variant nests when its field names a marked enum in `module_path`, the
+    /// module that declares `outer`.
+    fn nested_child(
+        &mut self,
+        outer: &ItemEnum,
+        options: &EnumOptions,
+        module_path: &str,
+        variant: &syn::Variant,
+    ) -> Result<Option<NestedChild>, syn::Error> {
+        let external_path = VariantOptions::parse(&variant.attrs)?.external;
+        let Some(external_path) = external_path else {
+            let Some((index, field_ty, marked)) = nested_field(variant)? else {
+                return Ok(None);
+            };
+            let (enums_by_ident, marked_enums) = self.module_enums(variant.span(), module_path)?;
+            let (pointer, inner_ty) = split_pointer(field_ty, options);
+            if let Ok(inner_ident) = extract_simple_ident(&inner_ty)
+                && let Some(inner_enum) = enums_by_ident.get(&inner_ident.to_string())
+                && marked_enums.contains(&inner_ident.to_string())
+            {
+                return Ok(Some(NestedChild {
+                    variant: variant.ident.clone(),
+                    field_ty: inner_ty,
+                    enum_item: inner_enum.clone(),
+                    module_path: module_path.to_string(),
+                    pointer,
+                    fields: variant.fields.clone(),
+                    index,
+                }));
+            }
+            if marked {
+                return Err(syn::Error::new(
+                    inner_ty.span(),
+                    format!(
+                        "field marked #[nestum(nested)] on {}::{} is not a #[nestum] enum \
+in this module; use #[nestum(external = \"path::to::Enum\")] for enums declared elsewhere",
+                        outer.ident, variant.ident
+                    ),
+                ));
+            }
+            return Ok(None);
+        };
+
+        let (index, field_ty, _) = nested_field(variant)?.ok_or_else(|| {
+            syn::Error::new(
+                variant.span(),
+                format!(
+                    "variant {}::{} uses #[nestum(external = \"...\")], \
+    but is not a single-field tuple variant; mark the nested field with #[nestum(nested)]",
+                    outer.ident, variant.ident
+                ),
+            )
+        })?;
+        let (pointer, inner_ty) = split_pointer(field_ty, options);
+
+        let inner_ident = extract_simple_ident(&inner_ty).map_err(|_| {
+            syn::Error::new(
+                inner_ty.span(),
+                "nested enum type must be a simple ident when using #[nestum(external = \"...\")]; \
+    use a bare enum name in the field",
+            )
+        })?;
 
-    let inner_ty = match extract_single_tuple_type(variant) {
-        Ok(inner_ty) => inner_ty,
-        Err(_) => return Ok(None),
-    };
-    let inner_ident = extract_simple_ident(&inner_ty)?;
+        let external_ident = external_path
+            .segments
+            .last()
+            .map(|s| s.ident.clone())
+            .ok_or_else(|| {
+                syn::Error::new(
+                    external_path.span(),
+                    "external path must include an enum ident",
+                )
+            })?;
 
-    if enums_by_ident.get(&inner_ident.to_string()).is_none() {
-        return Err(syn::Error::new(
-            inner_ty.span(),
-            format!(
-                "inner enum {} not found for {}::{}; \
-ensure it is declared in the same module or use #[nestum(external = \"path::to::{}\")]",
-                inner_ident, outer_enum.ident, outer_variant, inner_ident
-            ),
-        ));
-    }
+        if inner_ident != external_ident {
+            return Err(syn::Error::new(
+                inner_ty.span(),
+                format!(
+                    "field type {} does not match external enum path {}; \
+    use the enum ident as the field type",
+                    inner_ident,
+                    external_path_to_string(&external_path),
+                ),
+            ));
+        }
+
+        let (inner_enum, inner_is_marked, inner_module) = self
+            .resolve_external_enum(&external_path, module_path)?
+            .ok_or_else(|| {
+                syn::Error::new(
+                    external_path.span(),
+                    format!(
+                        "external enum {} not found; \
+    ensure the module path exists and the enum is declared in that module",
+                        external_path_to_string(&external_path),
+                    ),
+                )
+            })?;
+
+        if !inner_is_marked {
+            return Err(syn::Error::new(
+                external_path.span(),
+                "external enum must be marked with #[nestum] to enable nesting",
+            ));
+        }
 
-    Ok(Some((inner_ident, Vec::new(), false)))
+        Ok(Some(NestedChild {
+            variant: variant.ident.clone(),
+            field_ty: inner_ty,
+            enum_item: inner_enum,
+            module_path: inner_module,
+            pointer,
+            fields: variant.fields.clone(),
+            index,
+        }))
+    }
 }
 
 fn ensure_inner_variant_exists(
@@ -1216,63 +5041,14 @@ fn ensure_inner_variant_exists(
     }
 }
 
-fn effective_module_idents(
-    module_path: &[syn::Ident],
-    explicit_crate: bool,
-    current_module: &str,
-) -> Vec<syn::Ident> {
-    if module_path.is_empty() {
-        return if current_module == "crate" {
-            Vec::new()
-        } else {
-            module_idents_from_str(current_module)
-        };
-    }
-
-    let mut segments = module_path
-        .iter()
-        .map(|s| s.to_string())
-        .collect::<Vec<_>>();
-    let is_crate = segments.first().map(|s| s.as_str()) == Some("crate");
-    if is_crate {
-        segments.remove(0);
-    }
-    let base = segments.join("::");
-    let full = if is_crate || explicit_crate {
-        if base.is_empty() {
-            "crate".to_string()
-        } else {
-            format!("crate::{base}")
-        }
-    } else if current_module == "crate" {
-        base
-    } else if base.is_empty() {
-        current_module.to_string()
-    } else {
-        format!("{current_module}::{base}")
-    };
-    module_idents_from_str(&full)
-}
-
-fn module_idents_from_str(path: &str) -> Vec<syn::Ident> {
-    if path.is_empty() || path == "crate" {
-        return Vec::new();
-    }
-    path.split("::")
-        .filter(|s| !s.is_empty())
-        .map(|s| syn::Ident::new(s, proc_macro2::Span::call_site()))
-        .collect()
-}
-
+/// Module path idents for a module key; keys of other crates' modules start with `::`.
 fn absolute_module_idents(module_path: &str) -> Vec<syn::Ident> {
     let mut segments: Vec<String> = module_path
         .split("::")
         .filter(|s| !s.is_empty())
         .map(|s| s.to_string())
         .collect();
-    if segments.is_empty() {
-        segments.push("crate".to_string());
-    } else if segments[0] != "crate" {
+    if !module_path.starts_with("::") && segments.first().map(String::as_str) != Some("crate") {
         segments.insert(0, "crate".to_string());
     }
 
@@ -1297,106 +5073,528 @@ fn build_path_from_idents(module_idents: Vec<syn::Ident>, tail: &[syn::Ident]) -
     }
 }
 
-enum NestumAttrKind {
-    None,
-    Empty,
-    WithArgs,
+fn has_nestum_attr(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("nestum"))
+}
+
+/// Options accepted by `#[nestum(...)]` on an enum.
+#[derive(Default)]
+struct EnumOptions {
+    /// Keep the enum at its own path and put the nested constructors in this module instead of a
+    /// shadow module named after the enum.
+    companion: Option<syn::Ident>,
+    /// Pointer types besides `Box`, `Rc` and `Arc` that nested variants may wrap their inner
+    /// enum in.
+    pointers: Vec<syn::Path>,
+    /// Generate a kind enum under this name, `<Enum>Kind` unless named.
+    kind: Option<syn::Ident>,
+    /// Generate `From` and `TryFrom` between the enum and the enums nested below it.
+    conversions: bool,
+    /// Generate `is_*`, `as_*` and `into_*` accessors for nested variants.
+    accessors: bool,
+    /// Generate `nested_path()`, `nested_path_str()` and `PATHS`.
+    paths: bool,
+    /// Generate `all()` and `ALL` for a tree of unit variants.
+    values: bool,
+    /// Generate `Display` for a tree of unit variants.
+    display: bool,
+    /// Generate `FromStr` for a tree of unit variants.
+    from_str: bool,
+    /// Case convention for variant names in string paths.
+    rename_all: RenameRule,
+    /// Separator between variant names in string paths instead of `::`.
+    separator: Option<String>,
+    /// Generate `Serialize` and `Deserialize` with a flat tag naming the leaf.
+    serde: bool,
+    /// Key of the tag instead of `type`.
+    tag: Option<String>,
+    /// Key of the leaf's fields instead of `data`.
+    content: Option<String>,
+    /// Generate visitor traits with one method per leaf.
+    visitor: bool,
+    /// Generate a selector enum naming leaves and subtrees.
+    selector: bool,
+    /// Generate a bitset of the leaves of a tree of unit variants.
+    set: bool,
+    /// Export a descriptor macro so other crates can nest the enum.
+    export: bool,
 }
 
-fn nestum_attr_kind(attrs: &[Attribute]) -> Result<NestumAttrKind, syn::Error> {
-    for attr in attrs.iter() {
-        if attr.path().is_ident("nestum") {
-            match &attr.meta {
-                Meta::Path(_) => return Ok(NestumAttrKind::Empty),
-                Meta::List(_) | Meta::NameValue(_) => return Ok(NestumAttrKind::WithArgs),
+/// Every option `#[nestum(...)]` accepts on an enum, in the order the error message lists them.
+const KNOWN_OPTIONS: &[&str] = &[
+    "companion",
+    "pointer",
+    "kind",
+    "conversions",
+    "accessors",
+    "paths",
+    "values",
+    "display",
+    "from_str",
+    "rename_all",
+    "separator",
+    "serde",
+    "tag",
+    "content",
+    "visitor",
+    "selector",
+    "set",
+    "export",
+];
+
+impl EnumOptions {
+    fn parse(
+        enum_ident: &syn::Ident,
+        metas: &Punctuated<Meta, Token![,]>,
+    ) -> Result<Self, syn::Error> {
+        let mut options = EnumOptions::default();
+        for meta in metas.iter() {
+            let key = meta.path();
+            let Some(name) = KNOWN_OPTIONS
+                .iter()
+                .copied()
+                .find(|name| key.is_ident(name))
+            else {
+                return Err(syn::Error::new(
+                    meta.span(),
+                    format!(
+                        "invalid #[nestum(...)] on enum {enum_ident}; unknown option {}. \
+Supported options: {}",
+                        quote!(#key).to_string().replace(' ', ""),
+                        KNOWN_OPTIONS.join(", ")
+                    ),
+                ));
+            };
+            match name {
+                "companion" => {
+                    options.companion = Some(match meta {
+                        Meta::Path(_) => {
+                            format_ident!("{}", to_snake_case(&enum_ident.to_string()))
+                        }
+                        Meta::NameValue(MetaNameValue { value, .. }) => {
+                            let name = option_str(value, "companion")?;
+                            name.parse().map_err(|_| {
+                                syn::Error::new(
+                                    name.span(),
+                                    "companion must be a module name, e.g. companion = \"event\"",
+                                )
+                            })?
+                        }
+                        Meta::List(_) => {
+                            return Err(syn::Error::new(
+                                meta.span(),
+                                "invalid companion option; use companion or companion = \"name\"",
+                            ));
+                        }
+                    });
+                }
+                "pointer" => {
+                    let Meta::NameValue(MetaNameValue { value, .. }) = meta else {
+                        return Err(syn::Error::new(
+                            meta.span(),
+                            "invalid pointer option; use pointer = \"path::to::Pointer\"",
+                        ));
+                    };
+                    let path = option_str(value, "pointer")?;
+                    options.pointers.push(path.parse().map_err(|_| {
+                        syn::Error::new(
+                            path.span(),
+                            "pointer must be a valid Rust path, e.g. \"crate::gc::Gc\"",
+                        )
+                    })?);
+                }
+                "kind" => {
+                    options.kind = Some(match meta {
+                        Meta::Path(_) => format_ident!("{}Kind", enum_ident),
+                        Meta::NameValue(MetaNameValue { value, .. }) => {
+                            let name = option_str(value, "kind")?;
+                            name.parse().map_err(|_| {
+                                syn::Error::new(
+                                    name.span(),
+                                    "kind must be a type name, e.g. kind = \"EventKind\"",
+                                )
+                            })?
+                        }
+                        Meta::List(_) => {
+                            return Err(syn::Error::new(
+                                meta.span(),
+                                "invalid kind option; use kind or kind = \"EventKind\"",
+                            ));
+                        }
+                    });
+                }
+                "rename_all" => {
+                    let Meta::NameValue(MetaNameValue { value, .. }) = meta else {
+                        return Err(syn::Error::new(
+                            meta.span(),
+                            "invalid rename_all option; use rename_all = \"snake_case\"",
+                        ));
+                    };
+                    options.rename_all = RenameRule::parse(&option_str(value, "rename_all")?)?;
+                }
+                "separator" => {
+                    let Meta::NameValue(MetaNameValue { value, .. }) = meta else {
+                        return Err(syn::Error::new(
+                            meta.span(),
+                            "invalid separator option; use separator = \".\"",
+                        ));
+                    };
+                    let separator = option_str(value, "separator")?;
+                    if separator.value().is_empty() {
+                        return Err(syn::Error::new(
+                            separator.span(),
+                            "separator must not be empty, e.g. separator = \".\"",
+                        ));
+                    }
+                    options.separator = Some(separator.value());
+                }
+                "tag" | "content" => {
+                    let Meta::NameValue(MetaNameValue { value, .. }) = meta else {
+                        return Err(syn::Error::new(
+                            meta.span(),
+                            format!("invalid {name} option; use {name} = \"key\""),
+                        ));
+                    };
+                    let value = Some(option_str(value, name)?.value());
+                    if name == "tag" {
+                        options.tag = value;
+                    } else {
+                        options.content = value;
+                    }
+                }
+                // The rest are flags.
+                _ => {
+                    let Meta::Path(_) = meta else {
+                        return Err(syn::Error::new(
+                            meta.span(),
+                            format!("invalid {name} option; use #[nestum({name})]"),
+                        ));
+                    };
+                    match name {
+                        "conversions" => options.conversions = true,
+                        "accessors" => options.accessors = true,
+                        "paths" => options.paths = true,
+                        "values" => options.values = true,
+                        "display" => options.display = true,
+                        "from_str" => options.from_str = true,
+                        "serde" => {
+                            if !cfg!(feature = "serde") {
+                                return Err(syn::Error::new(
+                                    meta.span(),
+                                    "#[nestum(serde)] requires the serde feature; \
+enable it with nestum = { features = [\"serde\"] }",
+                                ));
+                            }
+                            options.serde = true;
+                        }
+                        "visitor" => options.visitor = true,
+                        "selector" => options.selector = true,
+                        "set" => options.set = true,
+                        "export" => options.export = true,
+                        _ => unreachable!("option {name} is listed in KNOWN_OPTIONS"),
+                    }
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    /// Options of an enum read from source.
+    fn from_attrs(item: &ItemEnum) -> Result<Self, syn::Error> {
+        for attr in item.attrs.iter() {
+            if !attr.path().is_ident("nestum") {
+                continue;
+            }
+            if let Meta::List(list) = &attr.meta {
+                let metas =
+                    list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+                return EnumOptions::parse(&item.ident, &metas);
             }
         }
+        Ok(EnumOptions::default())
     }
-    Ok(NestumAttrKind::None)
 }
 
-fn parse_variant_external_path(attrs: &[Attribute]) -> Result<Option<syn::Path>, syn::Error> {
-    for attr in attrs.iter() {
-        if !attr.path().is_ident("nestum") {
-            continue;
+fn option_str(value: &syn::Expr, key: &str) -> Result<syn::LitStr, syn::Error> {
+    match value {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit_str),
+            ..
+        }) => Ok(lit_str.clone()),
+        _ => Err(syn::Error::new(
+            value.span(),
+            format!("{key} must be a string literal"),
+        )),
+    }
+}
+
+/// `DocumentsEvent` -> `documents_event`, `HTTPRequest` -> `http_request`.
+fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let prev_lower =
+                i > 0 && (chars[i - 1].is_lowercase() || chars[i - 1].is_ascii_digit());
+            let next_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            let prev_upper = i > 0 && chars[i - 1].is_uppercase();
+            if prev_lower || (prev_upper && next_lower) {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(*c);
         }
+    }
+    out
+}
 
-        match &attr.meta {
-            Meta::Path(_) => {
-                return Err(syn::Error::new(
-                    attr.span(),
-                    "invalid #[nestum] on variant; use #[nestum(external = \"path::to::Enum\")]",
-                ));
+/// Case convention for the variant names in a string path, as in serde's `rename_all`.
+#[derive(Clone, Copy, Default)]
+enum RenameRule {
+    /// Names as written.
+    #[default]
+    None,
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    const NAMES: &[(&str, RenameRule)] = &[
+        ("lowercase", RenameRule::Lower),
+        ("UPPERCASE", RenameRule::Upper),
+        ("PascalCase", RenameRule::Pascal),
+        ("camelCase", RenameRule::Camel),
+        ("snake_case", RenameRule::Snake),
+        ("SCREAMING_SNAKE_CASE", RenameRule::ScreamingSnake),
+        ("kebab-case", RenameRule::Kebab),
+        ("SCREAMING-KEBAB-CASE", RenameRule::ScreamingKebab),
+    ];
+
+    fn parse(lit: &syn::LitStr) -> Result<Self, syn::Error> {
+        let value = lit.value();
+        RenameRule::NAMES
+            .iter()
+            .find(|(name, _)| *name == value)
+            .map(|(_, rule)| *rule)
+            .ok_or_else(|| {
+                let names: Vec<_> = RenameRule::NAMES.iter().map(|(name, _)| *name).collect();
+                syn::Error::new(
+                    lit.span(),
+                    format!(
+                        "unknown rename_all rule \"{value}\"; expected one of {}",
+                        names.join(", ")
+                    ),
+                )
+            })
+    }
+
+    /// Renames a `PascalCase` variant name.
+    fn apply(self, name: &str) -> String {
+        match self {
+            RenameRule::None | RenameRule::Pascal => name.to_string(),
+            RenameRule::Lower => name.to_lowercase(),
+            RenameRule::Upper => name.to_uppercase(),
+            RenameRule::Camel => {
+                let mut chars = name.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_lowercase().chain(chars).collect())
+                    .unwrap_or_default()
             }
-            Meta::NameValue(_) => {
-                return Err(syn::Error::new(
-                    attr.span(),
-                    "invalid #[nestum(...)] on variant; expected external = \"path::to::Enum\"",
-                ));
+            RenameRule::Snake => to_snake_case(name),
+            RenameRule::ScreamingSnake => to_snake_case(name).to_uppercase(),
+            RenameRule::Kebab => to_snake_case(name).replace('_', "-"),
+            RenameRule::ScreamingKebab => to_snake_case(name).to_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+/// Options from `#[nestum(...)]` on a variant.
+#[derive(Default)]
+struct VariantOptions {
+    external: Option<syn::Path>,
+    /// Skips the variant's `From`/`TryFrom` impls, e.g. when another variant nests the same enum.
+    skip_from: bool,
+}
+
+impl VariantOptions {
+    fn parse(attrs: &[Attribute]) -> Result<Self, syn::Error> {
+        let mut options = VariantOptions::default();
+        for attr in attrs.iter() {
+            if !attr.path().is_ident("nestum") {
+                continue;
             }
-            Meta::List(list) => {
-                let metas =
-                    list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
-                if metas.is_empty() {
+
+            let list = match &attr.meta {
+                Meta::Path(_) => {
                     return Err(syn::Error::new(
                         attr.span(),
                         "invalid #[nestum] on variant; use #[nestum(external = \"path::to::Enum\")]",
                     ));
                 }
-
-                for meta in metas.iter() {
-                    if let Meta::NameValue(MetaNameValue { path, value, .. }) = meta {
-                        if path.is_ident("external") {
-                            let lit = match value {
-                                syn::Expr::Lit(expr_lit) => expr_lit.lit.clone(),
-                                _ => {
-                                    return Err(syn::Error::new(
-                                        value.span(),
-                                        "external must be a string literal",
-                                    ));
-                                }
-                            };
-                            let path_str = match lit {
-                                syn::Lit::Str(lit_str) => lit_str,
-                                _ => {
-                                    return Err(syn::Error::new(
-                                        value.span(),
-                                        "external must be a string literal",
-                                    ));
-                                }
-                            };
-                            let parsed: syn::Path =
-                                syn::parse_str(&path_str.value()).map_err(|_| {
-                                    syn::Error::new(
-                                        path_str.span(),
-                                        "external must be a valid Rust path, e.g. \"crate::foo::Enum\"",
-                                    )
-                                })?;
-                            return Ok(Some(parsed));
-                        }
-                    }
+                Meta::NameValue(_) => {
+                    return Err(syn::Error::new(
+                        attr.span(),
+                        "invalid #[nestum(...)] on variant; \
+expected external = \"path::to::Enum\" or skip_from",
+                    ));
                 }
-
+                Meta::List(list) => list,
+            };
+            let metas = list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+            if metas.is_empty() {
                 return Err(syn::Error::new(
                     attr.span(),
-                    "invalid #[nestum(...)] on variant; expected external = \"path::to::Enum\"",
+                    "invalid #[nestum] on variant; use #[nestum(external = \"path::to::Enum\")]",
                 ));
             }
+
+            for meta in metas.iter() {
+                match meta {
+                    Meta::NameValue(MetaNameValue { path, value, .. })
+                        if path.is_ident("external") =>
+                    {
+                        let path_str = match value {
+                            syn::Expr::Lit(syn::ExprLit {
+                                lit: syn::Lit::Str(lit_str),
+                                ..
+                            }) => lit_str,
+                            _ => {
+                                return Err(syn::Error::new(
+                                    value.span(),
+                                    "external must be a string literal",
+                                ));
+                            }
+                        };
+                        let parsed: syn::Path =
+                            syn::parse_str(&path_str.value()).map_err(|_| {
+                                syn::Error::new(
+                                    path_str.span(),
+                                    "external must be a valid Rust path, e.g. \"crate::foo::Enum\"",
+                                )
+                            })?;
+                        options.external = Some(parsed);
+                    }
+                    Meta::Path(path) if path.is_ident("skip_from") => options.skip_from = true,
+                    _ => {
+                        return Err(syn::Error::new(
+                            attr.span(),
+                            "invalid #[nestum(...)] on variant; \
+expected external = \"path::to::Enum\" or skip_from",
+                        ));
+                    }
+                }
+            }
         }
-    }
 
-    Ok(None)
+        Ok(options)
+    }
 }
 
-fn extract_single_tuple_type(variant: &syn::Variant) -> Result<syn::Type, syn::Error> {
+/// The field of `variant` that may hold a nested enum: the one marked `#[nestum(nested)]`, or
+/// the only field of a single-field tuple variant. Also returns whether the field was marked.
+fn nested_field(variant: &syn::Variant) -> Result<Option<(usize, syn::Type, bool)>, syn::Error> {
+    let mut marked = None;
+    for (index, field) in variant.fields.iter().enumerate() {
+        if !parse_field_nested_attr(&field.attrs)? {
+            continue;
+        }
+        if marked.is_some() {
+            return Err(syn::Error::new(
+                field.span(),
+                format!(
+                    "variant {} has more than one #[nestum(nested)] field; \
+mark only the field that holds the inner enum",
+                    variant.ident
+                ),
+            ));
+        }
+        marked = Some((index, field.ty.clone(), true));
+    }
+    if marked.is_some() {
+        return Ok(marked);
+    }
+
     match &variant.fields {
         syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
-            Ok(fields.unnamed.first().unwrap().ty.clone())
+            Ok(Some((0, fields.unnamed[0].ty.clone(), false)))
         }
-        _ => Err(syn::Error::new(
-            variant.span(),
-            "nested variants must be tuple variants with exactly one field",
-        )),
+        _ => Ok(None),
+    }
+}
+
+/// Whether a field carries `#[nestum(nested)]`.
+fn parse_field_nested_attr(attrs: &[Attribute]) -> Result<bool, syn::Error> {
+    let mut nested = false;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("nestum")) {
+        let is_nested = match &attr.meta {
+            Meta::List(list) => list
+                .parse_args::<syn::Path>()
+                .is_ok_and(|path| path.is_ident("nested")),
+            _ => false,
+        };
+        if !is_nested {
+            return Err(syn::Error::new(
+                attr.span(),
+                "invalid #[nestum] on field; use #[nestum(nested)] to mark the field that holds \
+the inner enum",
+            ));
+        }
+        nested = true;
+    }
+    Ok(nested)
+}
+
+/// The fields of a nested variant other than the one holding the inner enum, in order.
+fn context_fields(fields: &Fields, index: usize) -> Vec<&syn::Field> {
+    fields
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .map(|(_, field)| field)
+        .collect()
+}
+
+/// A nested variant built from its context values (in field order) and the inner value, as an
+/// expression or pattern: `Path(ctx, inner)` or `Path { tenant: ctx, event: inner }`.
+fn nested_variant_tokens(
+    path: proc_macro2::TokenStream,
+    fields: &Fields,
+    index: usize,
+    context: Vec<proc_macro2::TokenStream>,
+    inner: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let mut context = context.into_iter();
+    let values: Vec<_> = (0..fields.len())
+        .map(|i| {
+            if i == index {
+                inner.clone()
+            } else {
+                context.next().unwrap_or_else(|| quote!(_))
+            }
+        })
+        .collect();
+    match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|field| &field.ident);
+            quote! { #path { #(#idents: #values),* } }
+        }
+        _ => quote! { #path(#(#values),*) },
+    }
+}
+
+/// Splits a nested field type into its optional smart pointer and the inner enum type.
+fn split_pointer(ty: syn::Type, options: &EnumOptions) -> (Option<Pointer>, syn::Type) {
+    match Pointer::split(&ty, options) {
+        Some((pointer, inner)) => (Some(pointer), inner),
+        None => (None, ty),
     }
 }
 
@@ -1422,8 +5620,6 @@ fn find_module_path_in_file_with_base(
     base: &str,
 ) -> Option<String> {
     let content = std::fs::read_to_string(file_path).ok()?;
-    let parsed = syn::parse_file(&content).ok()?;
-    let mut best_stack: Vec<String> = Vec::new();
 
     fn span_contains_line(span: proc_macro2::Span, line: usize) -> bool {
         let proc_macro2::LineColumn { line: start, .. } = span.start();
@@ -1450,7 +5646,11 @@ fn find_module_path_in_file_with_base(
         }
     }
 
-    visit_items(&parsed.items, line_number, &mut Vec::new(), &mut best_stack);
+    let best_stack = module_path_extractor::with_source_locations(&content, |parsed| {
+        let mut best_stack: Vec<String> = Vec::new();
+        visit_items(&parsed.items, line_number, &mut Vec::new(), &mut best_stack);
+        best_stack
+    })?;
 
     if best_stack.is_empty() {
         return Some(base.to_string());
@@ -1464,22 +5664,89 @@ fn find_module_path_in_file_with_base(
     }
 }
 
-fn current_module_context() -> Result<(String, std::path::PathBuf, String), syn::Error> {
-    let (file_path, line) = module_path_extractor::get_source_info().ok_or_else(|| {
+/// What a macro call is attached to, which locates it in source with the `stable` feature.
+#[cfg_attr(not(feature = "stable"), allow(dead_code))]
+enum CallSite<'a> {
+    /// `#[nestum]` on an item.
+    Item(&'a Item),
+    /// A function-like macro called by one of these names, with its input.
+    Macro(&'static [&'static str], proc_macro2::TokenStream),
+}
+
+#[cfg(not(feature = "stable"))]
+fn source_info(_: CallSite<'_>) -> Result<(String, usize), syn::Error> {
+    module_path_extractor::get_source_info().ok_or_else(|| {
         syn::Error::new(
             proc_macro2::Span::call_site(),
             "unable to locate source file for #[nestum]; \
-this macro requires nightly and proc_macro_span support",
+this macro requires nightly and proc_macro_span support, or the stable feature",
         )
-    })?;
+    })
+}
 
-    let module_root = module_path_extractor::module_root_from_file(&file_path);
-    let module_path = if module_root
-        .to_string_lossy()
-        .replace('\\', "/")
-        .ends_with("/src")
-    {
-        module_path_extractor::find_module_path_in_file(&file_path, line, &module_root).ok_or_else(
+/// Asks the compiler for the call's file and line, which Rust 1.88 and later report on stable.
+/// Older compilers report none, so the call is then found in the crate's source files. Several
+/// matches are fine as long as they all sit in the same module, since only the module matters
+/// for resolving paths.
+#[cfg(feature = "stable")]
+fn source_info(site: CallSite<'_>) -> Result<(String, usize), syn::Error> {
+    use module_path_extractor::CallSite as Site;
+
+    if let Some(info) = module_path_extractor::get_source_info() {
+        return Ok(info);
+    }
+    let sites = match &site {
+        CallSite::Item(item) => module_path_extractor::find_call_sites(&Site::Attribute {
+            attr: "nestum",
+            item,
+        }),
+        CallSite::Macro(names, tokens) => {
+            module_path_extractor::find_call_sites(&Site::Macro { names, tokens })
+        }
+    };
+    let mut modules: Vec<(String, usize, String)> = Vec::new();
+    for (file, line) in sites {
+        let Ok((_, module)) = module_context_at(&file, line) else {
+            continue;
+        };
+        if !modules.iter().any(|(_, _, seen)| *seen == module) {
+            modules.push((file, line, module));
+        }
+    }
+    match modules.as_slice() {
+        [(file, line, _)] => Ok((file.clone(), *line)),
+        [] => Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "unable to locate this macro call in the crate's source files; \
+before Rust 1.88, the stable feature only finds calls written in files reachable from the crate \
+root through mod declarations, not calls produced by other macros or renamed imports",
+        )),
+        found => {
+            let places = found
+                .iter()
+                .map(|(file, line, module)| format!("{file}:{line} ({module})"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!(
+                    "this macro call matches several places in different modules: {places}; \
+before Rust 1.88, the stable feature finds #[nestum] items by name and nested! calls by their \
+contents, so rename one of the items or make the calls differ"
+                ),
+            ))
+        }
+    }
+}
+
+/// The source root and module path for `line` of `file_path`.
+fn module_context_at(
+    file_path: &str,
+    line: usize,
+) -> Result<(std::path::PathBuf, String), syn::Error> {
+    let module_root = module_path_extractor::module_root_from_file(file_path);
+    let module_path = if module_root.ends_with("src") {
+        module_path_extractor::find_module_path_in_file(file_path, line, &module_root).ok_or_else(
             || {
                 syn::Error::new(
                     proc_macro2::Span::call_site(),
@@ -1489,7 +5756,7 @@ ensure the enum is in a regular Rust module file (not generated or included)",
             },
         )?
     } else {
-        find_module_path_in_file_with_base(&file_path, line, "crate").ok_or_else(|| {
+        find_module_path_in_file_with_base(file_path, line, "crate").ok_or_else(|| {
             syn::Error::new(
                 proc_macro2::Span::call_site(),
                 "unable to determine module path for #[nestum]; \
@@ -1498,59 +5765,19 @@ ensure the enum is in a regular Rust module file (not generated or included)",
         })?
     };
 
-    Ok((file_path, module_root, module_path))
+    Ok((module_root, module_path))
 }
 
-fn load_module_enums(
-    span: proc_macro2::Span,
-    module_path: &str,
-    current_file: &str,
-    module_root: &std::path::Path,
-    cache: &mut HashMap<String, HashMap<String, ItemEnum>>,
-) -> Result<(), syn::Error> {
-    if cache.get(module_path).is_some() {
-        return Ok(());
-    }
-
-    let module_file =
-        module_path_extractor::module_path_to_file(module_path, current_file, module_root);
-    let module_file = match module_file {
-        Some(file) => file,
-        None => {
-            let all = collect_enums_by_module_path(current_file, module_root, current_file)?;
-            if all.contains_key(module_path) {
-                for (module, enums) in all.into_iter() {
-                    cache.insert(module, enums);
-                }
-                return Ok(());
-            }
-            return Err(syn::Error::new(
-                span,
-                format!(
-                    "unable to locate module file for {module_path}; \
-expected {module_path}.rs or {module_path}/mod.rs under the module root"
-                ),
-            ));
-        }
-    };
-
-    let all = collect_enums_by_module_path(
-        module_file.to_string_lossy().as_ref(),
-        module_root,
-        current_file,
-    )?;
-    for (module, enums) in all.into_iter() {
-        cache.insert(module, enums);
-    }
-
-    Ok(())
-}
+/// Enums keyed by module path, then by ident.
+type EnumsByModule = HashMap<String, HashMap<String, ItemEnum>>;
 
+/// The enums declared in `file_path` and its inline modules, along with the inline modules that
+/// take items from `include!`.
 fn collect_enums_by_module_path(
     file_path: &str,
     module_root: &std::path::Path,
     current_file: &str,
-) -> Result<HashMap<String, HashMap<String, ItemEnum>>, syn::Error> {
+) -> Result<(EnumsByModule, HashSet<String>), syn::Error> {
     let content = std::fs::read_to_string(file_path).map_err(|err| {
         syn::Error::new(
             proc_macro2::Span::call_site(),
@@ -1565,15 +5792,10 @@ fn collect_enums_by_module_path(
         )
     })?;
 
-    let base = if file_path == current_file
-        && !module_root
-            .to_string_lossy()
-            .replace('\\', "/")
-            .ends_with("/src")
-    {
+    let base = if file_path == current_file && !module_root.ends_with("src") {
         "crate".to_string()
     } else {
-        module_path_extractor::module_path_from_file_with_root(file_path, module_root)
+        module_path_extractor::module_path_in_crate(file_path, current_file, module_root)
     };
     let mut map: HashMap<String, HashMap<String, ItemEnum>> = HashMap::new();
 
@@ -1594,6 +5816,7 @@ fn collect_enums_by_module_path(
         stack: &mut Vec<String>,
         base: &str,
         map: &mut HashMap<String, HashMap<String, ItemEnum>>,
+        included: &mut HashSet<String>,
     ) {
         for item in items {
             match item {
@@ -1608,45 +5831,28 @@ fn collect_enums_by_module_path(
                         continue;
                     };
                     stack.push(module.ident.to_string());
-                    visit_items(inner_items, stack, base, map);
+                    visit_items(inner_items, stack, base, map, included);
                     stack.pop();
                 }
+                syn::Item::Macro(item_macro)
+                    if !stack.is_empty() && item_macro.mac.path.is_ident("include") =>
+                {
+                    included.insert(join_module_path(base, stack));
+                }
                 _ => {}
             }
         }
     }
 
-    visit_items(&parsed.items, &mut Vec::new(), &base, &mut map);
-    Ok(map)
-}
-
-fn resolve_external_enum(
-    path: &syn::Path,
-    current_file: &str,
-    module_root: &std::path::Path,
-    cache: &mut HashMap<String, HashMap<String, ItemEnum>>,
-) -> Result<Option<(ItemEnum, bool)>, syn::Error> {
-    let (module_path, enum_ident) = split_module_and_ident(path).ok_or_else(|| {
-        syn::Error::new(
-            path.span(),
-            "external path must include an enum ident, e.g. crate::foo::Enum",
-        )
-    })?;
-
-    load_module_enums(path.span(), &module_path, current_file, module_root, cache)?;
-
-    let enums = match cache.get(&module_path) {
-        Some(enums) => enums,
-        None => return Ok(None),
-    };
-
-    let item_enum = match enums.get(&enum_ident) {
-        Some(item_enum) => item_enum.clone(),
-        None => return Ok(None),
-    };
-
-    let marked = matches!(nestum_attr_kind(&item_enum.attrs)?, NestumAttrKind::Empty);
-    Ok(Some((item_enum, marked)))
+    let mut included = HashSet::new();
+    visit_items(
+        &parsed.items,
+        &mut Vec::new(),
+        &base,
+        &mut map,
+        &mut included,
+    );
+    Ok((map, included))
 }
 
 fn split_module_and_ident(path: &syn::Path) -> Option<(String, String)> {
@@ -1677,5 +5883,3 @@ fn external_path_to_string(path: &syn::Path) -> String {
         .collect::<Vec<_>>()
         .join("::")
 }
-
-// module loading handled by load_module_enums()
@@ -0,0 +1,15 @@
+use nestum::nestum;
+
+#[nestum]
+#[derive(Debug, PartialEq)]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum(export)]
+#[derive(Debug, PartialEq)]
+pub enum DocumentsEvent {
+    Lifecycle(LifecycleEvent),
+    Update { id: u32 },
+}
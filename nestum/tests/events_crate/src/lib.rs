@@ -0,0 +1,16 @@
+use nestum::nestum;
+
+pub mod documents;
+
+pub use documents::DocumentsEvent;
+
+#[nestum(export)]
+#[derive(Debug, PartialEq)]
+pub enum AuditEvent {
+    // Enums nesting this one usually reach DocumentsEvent directly as well.
+    #[nestum(external = "crate::documents::DocumentsEvent", skip_from)]
+    Documents(DocumentsEvent),
+    Login {
+        user: u32,
+    },
+}
@@ -2,6 +2,7 @@
 fn ui() {
     let t = trybuild::TestCases::new();
     t.pass("tests/ui/pass_basic.rs");
+    t.pass("tests/ui/pass_baseline.rs");
     t.pass("tests/ui/pass_external.rs");
     t.pass("tests/ui/pass_external_cross_file.rs");
     t.pass("tests/ui/pass_match.rs");
@@ -10,6 +11,34 @@ fn ui() {
     t.pass("tests/ui/pass_match_or.rs");
     t.pass("tests/ui/pass_match_module.rs");
     t.pass("tests/ui/pass_match_mixed.rs");
+    t.pass("tests/ui/pass_generic.rs");
+    t.pass("tests/ui/pass_foreign_types.rs");
+    t.pass("tests/ui/pass_deep.rs");
+    t.pass("tests/ui/pass_match_deep.rs");
+    t.pass("tests/ui/pass_match_forms.rs");
+    t.pass("tests/ui/pass_match_compound.rs");
+    t.pass("tests/ui/pass_match_subtree.rs");
+    t.pass("tests/ui/pass_match_bodies.rs");
+    t.pass("tests/ui/pass_nested_attr.rs");
+    t.pass("tests/ui/pass_struct_literal.rs");
+    t.pass("tests/ui/pass_companion.rs");
+    t.pass("tests/ui/pass_pointer.rs");
+    t.pass("tests/ui/pass_context.rs");
+    t.pass("tests/ui/pass_from.rs");
+    t.pass("tests/ui/pass_accessors.rs");
+    t.pass("tests/ui/pass_kind.rs");
+    t.pass("tests/ui/pass_kind_empty.rs");
+    t.pass("tests/ui/pass_paths.rs");
+    t.pass("tests/ui/pass_strings.rs");
+    t.pass("tests/ui/pass_visitor.rs");
+    t.pass("tests/ui/pass_selector.rs");
+    t.pass("tests/ui/pass_set.rs");
+    t.pass("tests/ui/pass_values.rs");
+    t.pass("tests/ui/pass_export.rs");
+    t.pass("tests/ui/pass_path_attr.rs");
+    t.pass("tests/ui/pass_path_attr_external.rs");
+    #[cfg(feature = "serde")]
+    t.pass("tests/ui/pass_serde.rs");
     t.compile_fail("tests/ui/fail_enum_args.rs");
     t.compile_fail("tests/ui/fail_variant_attr.rs");
     t.compile_fail("tests/ui/fail_external_not_tuple.rs");
@@ -22,7 +51,18 @@ fn ui() {
     t.compile_fail("tests/ui/fail_external_empty_args.rs");
     t.compile_fail("tests/ui/fail_external_type_not_ident.rs");
     t.compile_fail("tests/ui/fail_external_not_marked.rs");
-    t.compile_fail("tests/ui/fail_path_attr.rs");
+    t.compile_fail("tests/ui/fail_path_attr_not_found.rs");
     t.compile_fail("tests/ui/fail_include.rs");
     t.compile_fail("tests/ui/fail_cfg.rs");
+    t.compile_fail("tests/ui/fail_match_not_nested.rs");
+    t.compile_fail("tests/ui/fail_struct_literal_field.rs");
+    t.compile_fail("tests/ui/fail_struct_literal_base.rs");
+    t.compile_fail("tests/ui/fail_pointer_pattern.rs");
+    t.compile_fail("tests/ui/fail_pointer_exhaustive.rs");
+    t.compile_fail("tests/ui/fail_nested_field.rs");
+    t.compile_fail("tests/ui/fail_conversions_ambiguous.rs");
+    t.compile_fail("tests/ui/fail_strings_fields.rs");
+    t.compile_fail("tests/ui/fail_values_fields.rs");
+    #[cfg(not(feature = "serde"))]
+    t.compile_fail("tests/ui/fail_serde_feature.rs");
 }
@@ -0,0 +1,15 @@
+use nestum::nestum;
+
+#[nestum]
+pub enum ImagesEvent {
+    Resized(u32),
+}
+
+// Both variants nest `ImagesEvent`, and neither is marked `#[nestum(skip_from)]`.
+#[nestum(conversions)]
+pub enum Event {
+    Images(ImagesEvent),
+    Thumbnails(ImagesEvent),
+}
+
+fn main() {}
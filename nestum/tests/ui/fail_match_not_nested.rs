@@ -0,0 +1,23 @@
+use nestum::{nested, nestum};
+
+#[nestum]
+pub enum DocumentsEvent {
+    Created(u32),
+    Deleted,
+}
+
+#[nestum]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Other(u32),
+}
+
+fn main() {
+    let event = Event::Event::Other(1);
+    nested! {
+        match event {
+            Event::Other::Created(_) => {}
+            _ => {}
+        }
+    }
+}
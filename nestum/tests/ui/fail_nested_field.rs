@@ -0,0 +1,10 @@
+use nestum::nestum;
+
+pub struct TenantId(u32);
+
+#[nestum]
+pub enum Event {
+    Documents(#[nestum(nested)] TenantId, u32),
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use nestum::nestum;
+
+// The `#[path]` file exists, but does not declare the enum.
+#[path = "path_attr/platform.rs"]
+mod platform;
+
+#[nestum]
+pub enum Event {
+    #[nestum(external = "crate::platform::NetworkEvent")]
+    Network(NetworkEvent),
+}
+
+fn main() {}
@@ -0,0 +1,34 @@
+use nestum::{nested, nestum};
+
+#[nestum]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum]
+pub enum Event {
+    Lifecycle(Box<LifecycleEvent>),
+    Ping,
+}
+
+fn id(event: Event::Event) -> u32 {
+    nested! {
+        // `Purged` is missing; arms through the `Box` do not count towards exhaustiveness.
+        match event {
+            Event::Lifecycle::Archived(id) => *id,
+            Event::Ping => 0,
+        }
+    }
+}
+
+fn lifecycle(event: Event::Event) {
+    nested! {
+        let Event::Lifecycle::Archived(_id) = event;
+    }
+}
+
+fn main() {
+    let _ = id(Event::Event::Ping);
+    lifecycle(Event::Event::Ping);
+}
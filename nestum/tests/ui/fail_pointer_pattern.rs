@@ -0,0 +1,22 @@
+use nestum::{nested, nestum};
+
+#[nestum]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum]
+pub enum Event {
+    Lifecycle(Box<LifecycleEvent>),
+}
+
+fn main() {
+    let events = vec![Event::Lifecycle::Archived(1)];
+    nested! {
+        let ids: Vec<u32> = events
+            .iter()
+            .map(|Event::Lifecycle::Archived(id)| *id)
+            .collect();
+    }
+}
@@ -0,0 +1,8 @@
+use nestum::nestum;
+
+#[nestum(serde)]
+pub enum Event {
+    Ping,
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+use nestum::{nested, nestum};
+
+#[nestum]
+pub enum Inner {
+    Struct { x: i32, y: i32 },
+}
+
+#[nestum]
+pub enum Outer {
+    Wrap(Inner),
+    Plain(u8),
+}
+
+fn main() {
+    // `base` could be `Outer::Plain`, which has no `y` to take.
+    let base = Outer::Outer::Plain(1);
+    let _ = nested!(Outer::Wrap::Struct { x: 2, ..base });
+}
@@ -0,0 +1,15 @@
+use nestum::{nested, nestum};
+
+#[nestum]
+pub enum Inner {
+    Struct { x: i32 },
+}
+
+#[nestum]
+pub enum Outer {
+    Wrap(Inner),
+}
+
+fn main() {
+    let _ = nested!(Outer::Wrap::Struct { x: 1, y: 2 });
+}
@@ -0,0 +1,14 @@
+use nestum::nestum;
+
+#[nestum]
+pub enum DocumentsEvent {
+    Created(String),
+    Deleted,
+}
+
+#[nestum(values)]
+pub enum Event {
+    Documents(DocumentsEvent),
+}
+
+fn main() {}
@@ -0,0 +1,107 @@
+use nestum::nestum;
+use std::rc::Rc;
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum(accessors)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentsEvent {
+    Lifecycle(Box<LifecycleEvent>),
+    Update(String),
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImagesEvent {
+    Resized { width: u32 },
+}
+
+#[nestum(accessors)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Images(u64, #[nestum(nested)] ImagesEvent),
+    Shared(Rc<LifecycleEvent>),
+    Ping,
+}
+
+#[nestum]
+#[derive(Debug, PartialEq)]
+pub enum Wrapper<T> {
+    Inner(T),
+}
+
+#[nestum(accessors)]
+#[derive(Debug, PartialEq)]
+pub enum Tagged<T> {
+    Value(Wrapper<T>),
+    Other(T),
+}
+
+fn main() {
+    let mut event = Event::Documents::Update("doc".to_string());
+    assert!(event.is_documents());
+    assert!(!event.is_images());
+    assert_eq!(
+        event.as_documents(),
+        Some(&DocumentsEvent::DocumentsEvent::Update("doc".to_string()))
+    );
+    assert_eq!(event.as_images(), None);
+
+    if let Some(documents) = event.as_documents_mut() {
+        *documents = DocumentsEvent::Lifecycle::Archived(1);
+    }
+    assert_eq!(event, Event::Documents::Lifecycle::Archived(1));
+
+    // Leaf predicates cover every level, including through pointers.
+    assert!(event.is_documents_lifecycle());
+    assert!(event.is_documents_lifecycle_archived());
+    assert!(!event.is_documents_lifecycle_purged());
+    assert!(!event.is_documents_update());
+    assert!(!event.is_shared_archived());
+
+    let documents = event.clone().into_documents().unwrap();
+    assert_eq!(
+        documents.as_lifecycle(),
+        Some(&LifecycleEvent::LifecycleEvent::Archived(1))
+    );
+    let mut documents = documents;
+    if let Some(lifecycle) = documents.as_lifecycle_mut() {
+        *lifecycle = LifecycleEvent::LifecycleEvent::Purged;
+    }
+    assert_eq!(
+        documents.into_lifecycle(),
+        Ok(LifecycleEvent::LifecycleEvent::Purged)
+    );
+    assert_eq!(
+        Event::Event::Ping.into_documents(),
+        Err(Event::Event::Ping)
+    );
+
+    // Context fields are skipped: the accessors return the nested enum.
+    let images = Event::Images::Resized(9, 640);
+    assert!(images.is_images_resized());
+    assert_eq!(
+        images.as_images(),
+        Some(&ImagesEvent::ImagesEvent::Resized { width: 640 })
+    );
+
+    // Shared pointers only get `is_*` and `as_*`.
+    let shared = Event::Shared::Purged();
+    assert!(shared.is_shared());
+    assert!(shared.is_shared_purged());
+    assert_eq!(
+        shared.as_shared(),
+        Some(&LifecycleEvent::LifecycleEvent::Purged)
+    );
+
+    let tagged = Tagged::Value::Inner(5u8);
+    assert!(tagged.is_value_inner());
+    assert_eq!(tagged.as_value(), Some(&Wrapper::Wrapper::Inner(5)));
+    assert_eq!(tagged.into_value(), Ok(Wrapper::Wrapper::Inner(5)));
+}
@@ -0,0 +1,62 @@
+use nestum::nestum;
+
+// Without options, nestum generates nothing that could clash with the enum's own items.
+#[nestum]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentsEvent {
+    Created,
+    Deleted,
+}
+
+#[nestum]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Ping,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EventKind {
+    Documents,
+    Ping,
+}
+
+impl Event::Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::Documents(_) => EventKind::Documents,
+            Self::Ping => EventKind::Ping,
+        }
+    }
+
+    pub const ALL: [Self; 2] = [Self::Documents(DocumentsEvent::DocumentsEvent::Created), Self::Ping];
+    pub const PATHS: &'static [&'static str] = &["Documents", "Ping"];
+
+    pub fn is_documents(&self) -> bool {
+        matches!(self, Self::Documents(_))
+    }
+
+    pub fn nested_path(&self) -> &'static [&'static str] {
+        match self {
+            Self::Documents(_) => &Self::PATHS[..1],
+            Self::Ping => &Self::PATHS[1..],
+        }
+    }
+}
+
+impl From<DocumentsEvent::DocumentsEvent> for Event::Event {
+    fn from(_: DocumentsEvent::DocumentsEvent) -> Self {
+        Self::Ping
+    }
+}
+
+fn main() {
+    assert_eq!(Event::Documents::Created.kind(), EventKind::Documents);
+    assert_eq!(Event::Event::Ping.kind(), EventKind::Ping);
+
+    let event: Event::Event = DocumentsEvent::DocumentsEvent::Created.into();
+    assert_eq!(event, Event::Event::Ping);
+    assert!(!event.is_documents());
+    assert_eq!(event.nested_path(), ["Ping"]);
+    assert_eq!(Event::Event::ALL.len(), 2);
+}
@@ -0,0 +1,68 @@
+use nestum::{nested, nestum};
+
+mod lifecycle {
+    use nestum::nestum;
+
+    #[nestum(companion)]
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum LifecycleEvent {
+        Archived(u32),
+        Purged,
+    }
+}
+
+#[nestum(companion)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentsEvent {
+    #[nestum(external = "crate::lifecycle::LifecycleEvent")]
+    Lifecycle(lifecycle::LifecycleEvent),
+    Update(String),
+}
+
+#[nestum(companion = "ev")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Ping,
+}
+
+// Shadow-module enums can nest companion ones and the other way round.
+#[nestum]
+pub enum Envelope {
+    Event(Event),
+}
+
+impl Event {
+    fn is_ping(&self) -> bool {
+        matches!(self, Event::Ping)
+    }
+}
+
+fn describe(event: &Event) -> u32 {
+    nested! {
+        match event {
+            Event::Documents::Lifecycle::Archived(id) => *id,
+            ev::Documents::Lifecycle::Purged => 1,
+            Event::Documents::Update(doc) => doc.len() as u32,
+            Event::Ping => 0,
+        }
+    }
+}
+
+fn main() {
+    let archived: Event = ev::Documents::Lifecycle::Archived(7);
+    assert_eq!(
+        archived,
+        Event::Documents(DocumentsEvent::Lifecycle(lifecycle::LifecycleEvent::Archived(7)))
+    );
+    assert_eq!(describe(&archived), 7);
+    assert_eq!(describe(&ev::Documents::Lifecycle::Purged), 1);
+    assert_eq!(describe(&ev::Documents::Update("doc".to_string())), 3);
+    assert!(Event::Ping.is_ping());
+
+    let _: DocumentsEvent = documents_event::Lifecycle::Purged;
+    let _: lifecycle::LifecycleEvent = lifecycle::LifecycleEvent::Purged;
+
+    let envelope = Envelope::Event::Documents::Update("doc".to_string());
+    assert!(nested!(matches!(envelope, Envelope::Event::Documents::Update(_))));
+}
@@ -0,0 +1,107 @@
+use nestum::{nested, nestum};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TenantId(u32);
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Moved { to: String },
+    Purged,
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentsEvent {
+    Lifecycle {
+        at: u64,
+        #[nestum(nested)]
+        event: LifecycleEvent,
+    },
+    Update(String),
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Documents(TenantId, #[nestum(nested)] DocumentsEvent),
+    Ping,
+}
+
+fn describe(event: &Event::Event) -> String {
+    nested! {
+        match event {
+            Event::Documents::Update(TenantId(0), _) => "system update".to_string(),
+            Event::Documents::Update(tenant, doc) => format!("{} updated {doc}", tenant.0),
+            Event::Documents::Lifecycle::Archived(tenant, at, id) => {
+                format!("{} archived {id} at {at}", tenant.0)
+            }
+            Event::Documents::Lifecycle::Moved { at, to } => format!("moved to {to} at {at}"),
+            Event::Documents::Lifecycle::Purged(..) => "purged".to_string(),
+            Event::Ping => "ping".to_string(),
+        }
+    }
+}
+
+fn tenant_of(event: &Event::Event) -> Option<TenantId> {
+    nested! {
+        match event {
+            // Only the leaf's own fields: the context fields match anything.
+            Event::Documents::Update(_) => None,
+            Event::Documents(tenant, _) => Some(*tenant),
+            Event::Ping => None,
+        }
+    }
+}
+
+fn main() {
+    let tenant = TenantId(7);
+
+    let update = Event::Documents::Update(tenant, "doc".to_string());
+    assert_eq!(
+        update,
+        Event::Event::Documents(tenant, DocumentsEvent::DocumentsEvent::Update("doc".to_string()))
+    );
+    assert_eq!(describe(&update), "7 updated doc");
+    assert_eq!(
+        describe(&Event::Documents::Update(TenantId(0), "doc".to_string())),
+        "system update"
+    );
+    assert_eq!(tenant_of(&update), None);
+
+    let archived = Event::Documents::Lifecycle::Archived(tenant, 100, 3);
+    assert_eq!(
+        archived,
+        Event::Event::Documents(
+            tenant,
+            DocumentsEvent::DocumentsEvent::Lifecycle {
+                at: 100,
+                event: LifecycleEvent::LifecycleEvent::Archived(3),
+            }
+        )
+    );
+    assert_eq!(describe(&archived), "7 archived 3 at 100");
+    assert_eq!(tenant_of(&archived), Some(tenant));
+
+    let purged = Event::Documents::Lifecycle::Purged(tenant, 200);
+    assert_eq!(describe(&purged), "purged");
+
+    let moved = Event::Documents::Lifecycle::Moved(tenant, 300, "a".to_string());
+    assert_eq!(describe(&moved), "moved to a at 300");
+
+    nested! {
+        let is_purged = matches!(purged, Event::Documents::Lifecycle::Purged);
+        assert!(is_purged);
+
+        // Named context fields can be set or bound by name in struct literals and patterns.
+        let moved_doc = DocumentsEvent::Lifecycle::Moved {
+            at: 400,
+            to: "b".to_string(),
+        };
+        let DocumentsEvent::Lifecycle::Moved { at, to } = moved_doc else {
+            panic!("expected moved");
+        };
+        assert_eq!((at, to.as_str()), (400, "b"));
+    }
+}
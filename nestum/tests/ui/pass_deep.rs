@@ -0,0 +1,60 @@
+use nestum::nestum;
+
+mod lifecycle {
+    use nestum::nestum;
+
+    #[nestum]
+    pub enum LifecycleEvent {
+        Archived(u32),
+        Restored { id: u32 },
+        Purged,
+    }
+}
+
+#[nestum]
+pub enum DocumentsEvent {
+    #[nestum(external = "crate::lifecycle::LifecycleEvent")]
+    Lifecycle(LifecycleEvent),
+    Update(String),
+}
+
+#[nestum]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Other,
+}
+
+#[nestum]
+pub enum Envelope {
+    Event(Event),
+}
+
+#[nestum]
+pub enum Leaf<T> {
+    Value(T),
+    Empty,
+}
+
+#[nestum]
+pub enum Branch<T> {
+    Leaf(Leaf<T>),
+}
+
+#[nestum]
+pub enum Tree<T> {
+    Branch(Branch<Vec<T>>),
+}
+
+fn main() {
+    let _: Event::Event = Event::Documents::Lifecycle::Archived(1);
+    let _: Event::Event = Event::Documents::Lifecycle::Restored(2);
+    let _: Event::Event = Event::Documents::Lifecycle::Purged;
+    let _: Event::Event = Event::Documents::Update("doc".to_string());
+
+    let _: Envelope::Envelope = Envelope::Event::Documents::Lifecycle::Purged;
+    let _: Envelope::Envelope = Envelope::Event::Documents::Lifecycle::Archived(3);
+    let _: Envelope::Envelope = Envelope::Event::Other;
+
+    let _: Tree::Tree<u8> = Tree::Branch::Leaf::Value(vec![1u8]);
+    let _ = Tree::Branch::Leaf::Empty::<u8>();
+}
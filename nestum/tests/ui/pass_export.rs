@@ -0,0 +1,51 @@
+use nestum::{nested, nested_matches, nestum};
+
+#[nestum(kind, conversions, paths)]
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    #[nestum(external = "events_crate::DocumentsEvent")]
+    Documents(DocumentsEvent),
+    #[nestum(external = "events_crate::AuditEvent")]
+    Audit(Box<AuditEvent>),
+    Ping,
+}
+
+fn describe(event: &Event::Event) -> String {
+    nested! {
+        match event {
+            Event::Documents::Lifecycle::Archived(id) => format!("archived {id}"),
+            Event::Documents::Lifecycle::Purged => "purged".to_string(),
+            Event::Documents::Update { id } => format!("updated {id}"),
+            Event::Audit::Documents::Update { .. } => "audited update".to_string(),
+            Event::Audit(_) => "audit".to_string(),
+            Event::Ping => "ping".to_string(),
+        }
+    }
+}
+
+fn main() {
+    let archived = Event::Documents::Lifecycle::Archived(1);
+    assert_eq!(describe(&archived), "archived 1");
+    assert_eq!(describe(&Event::Documents::Update(2)), "updated 2");
+    assert_eq!(describe(&Event::Audit::Documents::Update(3)), "audited update");
+    assert_eq!(describe(&Event::Audit::Login(4)), "audit");
+    assert!(nested_matches!(archived, Event::Documents::Lifecycle::Archived(1)));
+
+    let login = events_crate::AuditEvent::AuditEvent::Login { user: 5 };
+    assert_eq!(Event::Event::from(login), Event::Audit::Login(5));
+
+    assert_eq!(archived.kind(), EventKind::DocumentsLifecycleArchived);
+    assert_eq!(
+        Event::Event::PATHS,
+        [
+            "Documents::Lifecycle::Archived",
+            "Documents::Lifecycle::Purged",
+            "Documents::Update",
+            "Audit::Documents::Lifecycle::Archived",
+            "Audit::Documents::Lifecycle::Purged",
+            "Audit::Documents::Update",
+            "Audit::Login",
+            "Ping",
+        ]
+    );
+}
@@ -0,0 +1,24 @@
+use nestum::nestum;
+
+#[derive(Debug)]
+pub struct Document {
+    pub id: String,
+}
+
+#[nestum]
+pub enum DocumentsEvent {
+    Update(Document),
+    Delete(String),
+}
+
+#[nestum]
+pub enum Event {
+    Documents(DocumentsEvent),
+}
+
+fn main() {
+    let _ = Event::Documents::Update(Document {
+        id: "doc-1".to_string(),
+    });
+    let _ = Event::Documents::Delete("doc-1".to_string());
+}
@@ -0,0 +1,108 @@
+use nestum::nestum;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum(conversions)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentsEvent {
+    Lifecycle(Box<LifecycleEvent>),
+    Update(String),
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImagesEvent {
+    Resized(u32),
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditEvent {
+    Viewed(u32),
+}
+
+#[nestum(conversions)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Documents(DocumentsEvent),
+    // Both variants nest `ImagesEvent`; only `Images` converts.
+    Images(ImagesEvent),
+    #[nestum(skip_from)]
+    Thumbnails(ImagesEvent),
+    // `Rc` only converts inwards: `TryFrom` cannot move the value out.
+    Audit(Rc<AuditEvent>),
+    Ping,
+}
+
+#[nestum(conversions)]
+#[derive(Debug, PartialEq)]
+pub enum Tagged<T> {
+    Value(Wrapper<T>),
+    Other(T),
+}
+
+#[nestum]
+#[derive(Debug, PartialEq)]
+pub enum Wrapper<T> {
+    Inner(T),
+}
+
+fn parse(input: &str) -> Result<u32, ImagesEvent::ImagesEvent> {
+    input
+        .parse()
+        .map_err(|_| ImagesEvent::ImagesEvent::Resized(0))
+}
+
+fn handle(input: &str) -> Result<u32, Event::Event> {
+    // `?` converts the inner error into the outer enum.
+    Ok(parse(input)?)
+}
+
+fn main() {
+    let event: Event::Event = DocumentsEvent::DocumentsEvent::Update("doc".to_string()).into();
+    assert_eq!(event, Event::Documents::Update("doc".to_string()));
+
+    // Conversions reach through every level, allocating pointers on the way.
+    let event = Event::Event::from(LifecycleEvent::LifecycleEvent::Archived(1));
+    assert_eq!(event, Event::Documents::Lifecycle::Archived(1));
+    let documents = DocumentsEvent::DocumentsEvent::from(LifecycleEvent::LifecycleEvent::Purged);
+    assert_eq!(documents, DocumentsEvent::Lifecycle::Purged());
+
+    assert_eq!(
+        LifecycleEvent::LifecycleEvent::try_from(event.clone()),
+        Ok(LifecycleEvent::LifecycleEvent::Archived(1))
+    );
+    assert_eq!(
+        DocumentsEvent::DocumentsEvent::try_from(event.clone()),
+        Ok(DocumentsEvent::Lifecycle::Archived(1))
+    );
+    // A mismatch hands the original value back.
+    let update = Event::Documents::Update("doc".to_string());
+    assert_eq!(LifecycleEvent::LifecycleEvent::try_from(update.clone()), Err(update));
+    assert_eq!(
+        DocumentsEvent::DocumentsEvent::try_from(Event::Event::Ping),
+        Err(Event::Event::Ping)
+    );
+
+    let resized: Event::Event = ImagesEvent::ImagesEvent::Resized(3).into();
+    assert_eq!(resized, Event::Images::Resized(3));
+    assert_eq!(handle("12"), Ok(12));
+    assert_eq!(handle("x"), Err(Event::Images::Resized(0)));
+
+    let viewed: Event::Event = AuditEvent::AuditEvent::Viewed(2).into();
+    assert_eq!(viewed, Event::Audit::Viewed(2));
+
+    let tagged: Tagged::Tagged<u8> = Wrapper::Wrapper::Inner(5).into();
+    assert_eq!(tagged, Tagged::Value::Inner(5));
+    assert_eq!(
+        Wrapper::Wrapper::try_from(Tagged::Tagged::Other(1u8)),
+        Err(Tagged::Tagged::Other(1))
+    );
+}
@@ -0,0 +1,65 @@
+use nestum::{nestum, nested};
+
+pub trait Payload {
+    fn id(&self) -> u32;
+}
+
+#[derive(Debug)]
+pub struct Document {
+    pub id: u32,
+}
+
+impl Payload for Document {
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+#[nestum]
+pub enum DocumentsEvent<P>
+where
+    P: Payload,
+{
+    Update(P),
+    Delete(u32),
+    Clear,
+}
+
+#[nestum]
+pub enum Borrowed<'a, T, const N: usize> {
+    Slice(&'a [T; N]),
+    Empty,
+}
+
+#[nestum]
+pub enum Event<'a, P: Payload, const N: usize = 2> {
+    Documents(DocumentsEvent<P>),
+    Raw(Borrowed<'a, u8, N>),
+    Other,
+}
+
+fn main() {
+    let event: Event::Event<'static, Document> =
+        Event::Documents::Update(Document { id: 1 });
+    nested! {
+        match event {
+            Event::Documents::Update(doc) => {
+                let _ = doc.id();
+            }
+            Event::Documents::Delete(id) => {
+                let _ = id;
+            }
+            Event::Documents::Clear => {}
+            Event::Raw::Slice(bytes) => {
+                let _ = bytes.len();
+            }
+            Event::Raw::Empty => {}
+            Event::Other => {}
+        }
+    }
+
+    let _ = Event::Documents::Clear::<Document, 2>();
+    let bytes = [1u8, 2];
+    let _: Event::Event<'_, Document> = Event::Raw::Slice(&bytes);
+    let _ = Event::Documents::Delete::<Document, 4>(7);
+}
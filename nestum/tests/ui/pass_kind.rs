@@ -0,0 +1,93 @@
+use nestum::nestum;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentsEvent {
+    Lifecycle(Rc<LifecycleEvent>),
+    Update { id: u32, title: String },
+}
+
+#[nestum(kind)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Images(u64, #[nestum(nested)] LifecycleEvent),
+    Ping,
+}
+
+// Recursive trees stop at the enum that is already being expanded.
+#[nestum]
+pub enum BinaryOp {
+    Add(crate::Expr::Expr, crate::Expr::Expr),
+}
+
+#[nestum(kind)]
+pub enum Expr {
+    Lit(i64),
+    Binary(Box<BinaryOp>),
+}
+
+#[nestum(companion, kind = "CommandType")]
+#[derive(Debug)]
+pub enum Command<T> {
+    Run(T),
+    Stop,
+}
+
+fn main() {
+    let event = Event::Documents::Lifecycle::Archived(1);
+    assert_eq!(event.kind(), EventKind::DocumentsLifecycleArchived);
+    assert_eq!(Event::Documents::Lifecycle::Purged().kind(), EventKind::DocumentsLifecyclePurged);
+    assert_eq!(
+        Event::Documents::Update(1, "doc".to_string()).kind(),
+        EventKind::DocumentsUpdate
+    );
+    assert_eq!(Event::Images::Purged(3).kind(), EventKind::ImagesPurged);
+    assert_eq!(Event::Event::Ping.kind(), EventKind::Ping);
+
+    assert_eq!(
+        EventKind::ALL,
+        [
+            EventKind::Documents,
+            EventKind::DocumentsLifecycle,
+            EventKind::DocumentsLifecycleArchived,
+            EventKind::DocumentsLifecyclePurged,
+            EventKind::DocumentsUpdate,
+            EventKind::Images,
+            EventKind::ImagesArchived,
+            EventKind::ImagesPurged,
+            EventKind::Ping,
+        ]
+    );
+    assert_eq!(
+        EventKind::DocumentsLifecyclePurged.parent(),
+        Some(EventKind::DocumentsLifecycle)
+    );
+    assert_eq!(EventKind::DocumentsLifecycle.parent(), Some(EventKind::Documents));
+    assert_eq!(EventKind::Documents.parent(), None);
+    assert!(EventKind::DocumentsUpdate.is_leaf());
+    assert!(!EventKind::DocumentsLifecycle.is_leaf());
+
+    // Kinds are `Copy + Hash`, e.g. for counters.
+    let mut counts = HashMap::new();
+    for event in [event.clone(), event, Event::Event::Ping] {
+        *counts.entry(event.kind()).or_insert(0) += 1;
+    }
+    assert_eq!(counts[&EventKind::DocumentsLifecycleArchived], 2);
+
+    let expr = Expr::Binary::Add(Expr::Expr::Lit(1), Expr::Expr::Lit(2));
+    assert_eq!(expr.kind(), ExprKind::BinaryAdd);
+    assert_eq!(ExprKind::ALL.len(), 3);
+
+    assert_eq!(Command::Run(1u8).kind(), CommandType::Run);
+    assert_eq!(CommandType::ALL, [CommandType::Run, CommandType::Stop]);
+}
@@ -0,0 +1,44 @@
+use nestum::nestum;
+
+// An inner enum with no variants has no leaves to match on.
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Unreachable {}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArchiveEvent {
+    Never(Box<Unreachable>),
+    Purged,
+}
+
+#[nestum(kind, visitor)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Never(Unreachable),
+    Archive(ArchiveEvent),
+    Ping,
+}
+
+struct Keep;
+
+impl EventFold for Keep {}
+
+fn main() {
+    assert_eq!(Event::Event::Ping.kind(), EventKind::Ping);
+    assert_eq!(Event::Archive::Purged.kind(), EventKind::ArchivePurged);
+    // Subtrees keep their kinds even when nothing below them can exist.
+    assert_eq!(
+        EventKind::ALL,
+        [
+            EventKind::Never,
+            EventKind::Archive,
+            EventKind::ArchiveNever,
+            EventKind::ArchivePurged,
+            EventKind::Ping,
+        ]
+    );
+
+    let folded = Event::Archive::Purged.fold(&mut Keep);
+    assert_eq!(folded, Event::Archive::Purged);
+}
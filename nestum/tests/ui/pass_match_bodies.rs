@@ -0,0 +1,78 @@
+use nestum::{nested, nestum};
+
+#[nestum]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum]
+pub enum DocumentsEvent {
+    Lifecycle(LifecycleEvent),
+    Update(String),
+}
+
+#[nestum]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Batch(Vec<u32>),
+}
+
+fn route(event: Event::Event, previous: Option<Event::Event>) -> u32 {
+    nested! {
+        match event {
+            Event::Documents::Lifecycle(inner) => match inner {
+                LifecycleEvent::Archived(id) => id,
+                LifecycleEvent::Purged => 0,
+            },
+            Event::Documents::Update(doc)
+                if matches!(previous, Some(Event::Documents::Lifecycle::Purged)) =>
+            {
+                doc.len() as u32 + 100
+            }
+            Event::Documents::Update(doc) => {
+                if let Some(Event::Documents::Lifecycle::Archived(id)) = previous {
+                    return id;
+                }
+                let total = previous
+                    .into_iter()
+                    .filter(|event| matches!(event, Event::Batch(_)))
+                    .count() as u32;
+                doc.len() as u32 + total
+            }
+            Event::Batch(ids) => ids
+                .iter()
+                .map(|id| match Event::Documents::Lifecycle::Archived(*id) {
+                    Event::Documents::Lifecycle::Archived(id) => id,
+                    _ => 0,
+                })
+                .sum(),
+        }
+    }
+}
+
+fn main() {
+    assert_eq!(route(Event::Documents::Lifecycle::Archived(4), None), 4);
+    assert_eq!(
+        route(
+            Event::Documents::Update("doc".to_string()),
+            Some(Event::Documents::Lifecycle::Purged)
+        ),
+        103
+    );
+    assert_eq!(
+        route(
+            Event::Documents::Update("doc".to_string()),
+            Some(Event::Documents::Lifecycle::Archived(9))
+        ),
+        9
+    );
+    assert_eq!(
+        route(
+            Event::Documents::Update("doc".to_string()),
+            Some(Event::Event::Batch(vec![]))
+        ),
+        4
+    );
+    assert_eq!(route(Event::Event::Batch(vec![1, 2]), None), 3);
+}
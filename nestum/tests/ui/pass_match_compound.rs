@@ -0,0 +1,82 @@
+use nestum::{nested, nestum};
+
+pub enum State {
+    Idle,
+    Busy,
+}
+
+#[nestum]
+pub enum DocumentsEvent {
+    Update(String),
+    Delete(u32),
+}
+
+#[nestum]
+pub enum ImagesEvent {
+    Delete(u32),
+}
+
+#[nestum]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Images(ImagesEvent),
+}
+
+pub struct Envelope {
+    pub event: Event::Event,
+}
+
+fn step(state: &State, event: &Event::Event) -> u32 {
+    nested! {
+        match (state, event) {
+            (State::Idle, Event::Documents::Update(doc)) => doc.len() as u32,
+            (State::Busy, &Event::Images::Delete(id)) => id,
+            (_, e @ Event::Documents::Delete(_)) => match e {
+                Event::Event::Documents(_) => 100,
+                _ => 0,
+            },
+            _ => 0,
+        }
+    }
+}
+
+fn main() {
+    assert_eq!(step(&State::Idle, &Event::Documents::Update("doc".to_string())), 3);
+    assert_eq!(step(&State::Busy, &Event::Images::Delete(7)), 7);
+    assert_eq!(step(&State::Busy, &Event::Documents::Delete(1)), 100);
+
+    let events = [Event::Images::Delete(1), Event::Documents::Delete(2)];
+    let total = nested! {
+        match &events {
+            [Event::Images::Delete(a), Event::Documents::Delete(b) | Event::Images::Delete(b)] => a + b,
+            _ => 0,
+        }
+    };
+    assert_eq!(total, 3);
+
+    let envelope = Envelope { event: Event::Documents::Delete(5) };
+    let id = nested! {
+        match envelope {
+            Envelope { event: Event::Documents::Delete(id) } => id,
+            _ => 0,
+        }
+    };
+    assert_eq!(id, 5);
+
+    let maybe = Some(Event::Images::Delete(9));
+    let id = nested! {
+        match maybe {
+            Some(Event::Images::Delete(id)) => id,
+            _ => 0,
+        }
+    };
+    assert_eq!(id, 9);
+
+    let boxed: Box<Event::Event> = Box::new(Event::Documents::Delete(3));
+    nested! {
+        let (Event::Documents::Delete(id), _): (Event::Event, u8) = (*boxed, 0) else {
+            panic!("expected a document delete");
+        };
+    }
+    assert_eq!(id, 3);
+}
@@ -0,0 +1,64 @@
+use nestum::{nested, nestum};
+
+mod lifecycle {
+    use nestum::nestum;
+
+    #[nestum]
+    pub enum LifecycleEvent {
+        Archived(u32),
+        Restored { id: u32 },
+        Purged,
+    }
+}
+
+#[nestum]
+pub enum DocumentsEvent {
+    #[nestum(external = "crate::lifecycle::LifecycleEvent")]
+    Lifecycle(LifecycleEvent),
+    Update(String),
+}
+
+#[nestum]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Other,
+}
+
+#[nestum]
+pub enum Envelope {
+    Event(Event),
+}
+
+fn describe(event: Event::Event) -> u32 {
+    nested! {
+        match event {
+            Event::Documents::Lifecycle::Archived(id) => id,
+            Event::Documents::Lifecycle::Restored { id } => id + 100,
+            Event::Documents::Lifecycle::Purged => 200,
+            Event::Documents::Update(_) => 300,
+            Event::Other => 400,
+        }
+    }
+}
+
+fn unwrap(envelope: Envelope::Envelope) -> u32 {
+    nested! {
+        match envelope {
+            Envelope::Event::Documents::Lifecycle::Archived(id)
+            | Envelope::Event::Documents::Lifecycle::Restored { id } => id,
+            crate::Envelope::Event::Documents::Lifecycle::Purged => 1,
+            Envelope::Event::Documents::Update(_) | Envelope::Event::Other => 2,
+        }
+    }
+}
+
+fn main() {
+    assert_eq!(describe(Event::Documents::Lifecycle::Archived(7)), 7);
+    assert_eq!(describe(Event::Documents::Lifecycle::Restored(7)), 107);
+    assert_eq!(describe(Event::Documents::Lifecycle::Purged), 200);
+    assert_eq!(describe(Event::Event::Other), 400);
+
+    assert_eq!(unwrap(Envelope::Event::Documents::Lifecycle::Restored(9)), 9);
+    assert_eq!(unwrap(Envelope::Event::Documents::Lifecycle::Purged), 1);
+    assert_eq!(unwrap(Envelope::Event::Other), 2);
+}
@@ -0,0 +1,90 @@
+use nestum::{nested, nested_matches, nestum};
+
+#[nestum]
+pub enum DocumentsEvent {
+    Update(String),
+    Delete { id: u32 },
+}
+
+#[nestum]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Ping,
+}
+
+fn id_of(event: &Event::Event) -> Option<u32> {
+    nested! {
+        let Event::Documents::Delete { id } = event else {
+            return None;
+        };
+    }
+    Some(*id)
+}
+
+mod util {
+    // Only `matches!` from `core` or `std` is rewritten; this one keeps its own syntax.
+    macro_rules! matches {
+        ($value:expr; $($candidate:expr),*) => {
+            [$($candidate),*].contains(&$value)
+        };
+    }
+    pub(crate) use matches;
+}
+
+#[nestum]
+fn is_small(id: u32, event: &Event::Event) -> bool {
+    util::matches!(id; 1, 2, 3) && std::matches!(event, Event::Documents::Delete { .. })
+}
+
+// Arguments of assert!, vec! and the other standard expression macros are rewritten too.
+#[nestum]
+fn check_update(event: &Event::Event) -> usize {
+    assert!(matches!(event, Event::Documents::Update(_)));
+    debug_assert!(!matches!(event, Event::Ping), "{} is not a ping", 1);
+    let deletes = vec![Event::Documents::Delete { id: 1 }, Event::Documents::Delete { id: 2 }];
+    assert_eq!(
+        deletes
+            .iter()
+            .filter(|event| matches!(event, Event::Documents::Delete { id } if *id > 1))
+            .count(),
+        1
+    );
+    let pings = vec![matches!(event, Event::Ping); 2];
+    assert_eq!(pings, [false, false]);
+    deletes.len()
+}
+
+fn main() {
+    let event = Event::Documents::Update("doc".to_string());
+
+    let name = nested! {
+        if let Event::Documents::Update(doc) = &event {
+            doc.len()
+        } else if let Event::Documents::Delete { id } = &event {
+            *id as usize
+        } else {
+            0
+        }
+    };
+    assert_eq!(name, 3);
+
+    let mut queue = vec![Event::Event::Ping, Event::Documents::Delete(1), Event::Documents::Delete(2)];
+    let mut deleted = 0;
+    nested! {
+        while let Event::Documents::Delete { id } = queue.pop().unwrap_or(Event::Event::Ping) {
+            deleted += id;
+        }
+    }
+    assert_eq!(deleted, 3);
+
+    assert_eq!(id_of(&Event::Documents::Delete(4)), Some(4));
+    assert_eq!(id_of(&Event::Event::Ping), None);
+
+    assert!(nested!(matches!(event, Event::Documents::Update(_))));
+    assert!(nested!(matches!(event, Event::Documents::Update(ref doc) if doc == "doc")));
+    assert!(!nested_matches!(event, Event::Ping | Event::Documents::Delete { .. }));
+    assert!(nested_matches!(Event::Documents::Delete(5), Event::Documents::Delete { id } if id > 4));
+    assert!(is_small(2, &Event::Documents::Delete(1)));
+    assert!(!is_small(4, &Event::Documents::Delete(1)));
+    assert_eq!(check_update(&event), 2);
+}
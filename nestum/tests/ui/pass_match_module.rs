@@ -20,6 +20,20 @@ mod api {
     }
 }
 
+// The same call in another module resolves on its own.
+mod client {
+    use nestum::nested;
+
+    pub fn check(value: crate::api::Outer::Outer) {
+        nested! {
+            match value {
+                crate::api::Outer::Wrap::A => {}
+            }
+        }
+    }
+}
+
 fn main() {
     api::check(api::Outer::Wrap::A);
+    client::check(api::Outer::Wrap::A);
 }
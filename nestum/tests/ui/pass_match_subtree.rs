@@ -0,0 +1,57 @@
+use nestum::{nested, nested_matches, nestum};
+
+#[nestum]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum]
+pub enum DocumentsEvent {
+    Lifecycle(LifecycleEvent),
+    Update(String),
+}
+
+#[nestum]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Ping,
+}
+
+fn lifecycle_id(event: LifecycleEvent::LifecycleEvent) -> u32 {
+    match event {
+        LifecycleEvent::LifecycleEvent::Archived(id) => id,
+        LifecycleEvent::LifecycleEvent::Purged => 0,
+    }
+}
+
+fn route(event: Event::Event) -> u32 {
+    nested! {
+        match event {
+            Event::Documents::Lifecycle(inner) => lifecycle_id(inner),
+            Event::Documents(DocumentsEvent::DocumentsEvent::Update(doc)) => doc.len() as u32,
+            Event::Ping => 1000,
+        }
+    }
+}
+
+fn main() {
+    assert_eq!(route(Event::Documents::Lifecycle::Archived(7)), 7);
+    assert_eq!(route(Event::Documents::Lifecycle::Purged), 0);
+    assert_eq!(route(Event::Documents::Update("doc".to_string())), 3);
+    assert_eq!(route(Event::Event::Ping), 1000);
+
+    let event = Event::Documents::Lifecycle::Purged;
+    assert!(nested_matches!(event, Event::Documents));
+    assert!(nested_matches!(event, Event::Documents::Lifecycle));
+    assert!(nested_matches!(event, Event::Documents::Lifecycle(..)));
+    assert!(!nested_matches!(event, Event::Ping));
+
+    let family = nested! {
+        match event {
+            docs @ Event::Documents => Some(docs),
+            _ => None,
+        }
+    };
+    assert!(family.is_some());
+}
@@ -0,0 +1,95 @@
+use nestum::nestum;
+
+#[nestum]
+pub enum DocumentsEvent {
+    Update(String),
+    Delete(u32),
+}
+
+#[nestum]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Ping,
+}
+
+#[nestum]
+fn describe(event: &Event::Event) -> u32 {
+    if let Event::Documents::Delete(id) = event {
+        return *id;
+    }
+    match event {
+        Event::Documents::Update(doc) => doc.len() as u32,
+        _ => 0,
+    }
+}
+
+pub struct Handler;
+
+#[nestum]
+impl Handler {
+    fn deleted(&self, events: &[Event::Event]) -> Vec<u32> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Documents::Delete(id) => Some(*id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn require_update(&self, event: Event::Event) -> Option<String> {
+        let Event::Documents::Update(doc) = event else {
+            return None;
+        };
+        Some(doc)
+    }
+}
+
+#[nestum]
+mod handlers {
+    use super::*;
+
+    pub fn is_ping(event: &Event::Event) -> bool {
+        matches!(event, super::Event::Ping)
+    }
+
+    pub mod local {
+        use nestum::nestum;
+
+        #[nestum]
+        pub enum Inner {
+            A,
+            B(u8),
+        }
+
+        #[nestum]
+        pub enum Outer {
+            Wrap(Inner),
+        }
+
+        pub fn value(outer: Outer::Outer) -> u8 {
+            match outer {
+                Outer::Wrap::A => 0,
+                Outer::Wrap::B(n) => n,
+            }
+        }
+    }
+}
+
+fn main() {
+    assert_eq!(describe(&Event::Documents::Delete(3)), 3);
+    assert_eq!(describe(&Event::Documents::Update("doc".to_string())), 3);
+    assert_eq!(describe(&Event::Event::Ping), 0);
+
+    let handler = Handler;
+    let events = [Event::Documents::Delete(1), Event::Event::Ping, Event::Documents::Delete(2)];
+    assert_eq!(handler.deleted(&events), vec![1, 2]);
+    assert_eq!(
+        handler.require_update(Event::Documents::Update("doc".to_string())),
+        Some("doc".to_string())
+    );
+    assert_eq!(handler.require_update(Event::Event::Ping), None);
+
+    assert!(handlers::is_ping(&Event::Event::Ping));
+    assert_eq!(handlers::local::value(handlers::local::Outer::Wrap::B(4)), 4);
+}
@@ -0,0 +1,26 @@
+use nestum::nestum;
+
+#[path = "path_attr/events.rs"]
+mod events;
+
+mod path_attr {
+    #[path = "platform.rs"]
+    pub mod platform;
+}
+
+#[nestum]
+pub enum Event {
+    #[nestum(external = "crate::events::DocumentsEvent")]
+    Documents(DocumentsEvent),
+    #[nestum(external = "crate::events::lifecycle::LifecycleEvent")]
+    Lifecycle(LifecycleEvent),
+    #[nestum(external = "crate::path_attr::platform::PlatformEvent")]
+    Platform(PlatformEvent),
+}
+
+fn main() {
+    let _ = Event::Documents::Created;
+    let _ = Event::Lifecycle::Archived;
+    let event = Event::Platform::Suspended;
+    assert!(nestum::nested_matches!(event, Event::Platform::Suspended));
+}
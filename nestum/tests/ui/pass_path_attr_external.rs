@@ -1,6 +1,6 @@
 use nestum::nestum;
 
-#[path = "missing_inner.rs"]
+#[path = "path_attr/inner.rs"]
 mod inner;
 
 #[nestum]
@@ -9,4 +9,6 @@ pub enum Outer {
     Wrap(Inner),
 }
 
-fn main() {}
+fn main() {
+    let _ = Outer::Wrap::A;
+}
@@ -0,0 +1,61 @@
+use nestum::nestum;
+
+#[nestum]
+#[derive(Debug)]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum(paths)]
+#[derive(Debug)]
+pub enum DocumentsEvent {
+    Lifecycle(Box<LifecycleEvent>),
+    Update { id: u32 },
+}
+
+#[nestum(paths)]
+#[derive(Debug)]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Ping,
+}
+
+#[nestum(companion, paths)]
+pub enum Tagged<T> {
+    Value(T),
+    Documents(DocumentsEvent),
+}
+
+fn main() {
+    let archived = Event::Documents::Lifecycle::Archived(1);
+    assert_eq!(archived.nested_path(), ["Documents", "Lifecycle", "Archived"]);
+    assert_eq!(archived.nested_path_str(), "Documents::Lifecycle::Archived");
+
+    let update = Event::Documents::Update(1);
+    assert_eq!(update.nested_path(), ["Documents", "Update"]);
+    assert_eq!(update.nested_path_str(), "Documents::Update");
+    assert_eq!(Event::Event::Ping.nested_path_str(), "Ping");
+
+    assert_eq!(
+        Event::Event::PATHS,
+        [
+            "Documents::Lifecycle::Archived",
+            "Documents::Lifecycle::Purged",
+            "Documents::Update",
+            "Ping",
+        ]
+    );
+    assert_eq!(DocumentsEvent::DocumentsEvent::PATHS.len(), 3);
+
+    assert_eq!(Tagged::Value(1u8).nested_path_str(), "Value");
+    assert_eq!(
+        Tagged::<u8>::PATHS,
+        [
+            "Value",
+            "Documents::Lifecycle::Archived",
+            "Documents::Lifecycle::Purged",
+            "Documents::Update",
+        ]
+    );
+}
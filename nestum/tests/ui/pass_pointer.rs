@@ -0,0 +1,188 @@
+use nestum::{nested, nested_matches, nestum};
+use std::rc::Rc;
+use std::sync::Arc;
+
+mod ptr {
+    /// A pointer type the crate does not know about.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Shared<T>(std::rc::Rc<T>);
+
+    impl<T> Shared<T> {
+        pub fn new(value: T) -> Self {
+            Shared(std::rc::Rc::new(value))
+        }
+    }
+
+    impl<T> std::ops::Deref for Shared<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            &self.0
+        }
+    }
+}
+
+// A recursive AST: `BinaryOp` holds `Expr`s and `Expr` boxes `BinaryOp`.
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryOp {
+    Add(crate::Expr::Expr, crate::Expr::Expr),
+    Mul(crate::Expr::Expr, crate::Expr::Expr),
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Lit(i64),
+    Binary(Box<BinaryOp>),
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentsEvent {
+    Lifecycle(Rc<LifecycleEvent>),
+    Update { id: u32, title: String },
+}
+
+#[nestum(pointer = "crate::ptr::Shared")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Documents(Arc<DocumentsEvent>),
+    Shared(ptr::Shared<LifecycleEvent>),
+    Ping,
+}
+
+fn eval(expr: &Expr::Expr) -> i64 {
+    nested! {
+        match expr {
+            Expr::Lit(value) => *value,
+            Expr::Binary::Add(lhs, rhs) => eval(lhs) + eval(rhs),
+            Expr::Binary::Mul(lhs, Expr::Lit(0)) | Expr::Binary::Mul(Expr::Lit(0), lhs) => {
+                let _ = lhs;
+                0
+            }
+            Expr::Binary::Mul(lhs, rhs) => eval(lhs) * eval(rhs),
+            // Arms through the `Box` do not count towards exhaustiveness.
+            Expr::Binary(_) => unreachable!(),
+        }
+    }
+}
+
+fn describe(event: Event::Event) -> String {
+    nested! {
+        match event {
+            Event::Documents::Lifecycle::Archived(id) if *id > 100 => format!("old {id}"),
+            Event::Documents::Lifecycle::Archived(id) => format!("archived {id}"),
+            Event::Documents::Lifecycle::Purged => "purged".to_string(),
+            Event::Documents::Update { id, title } => format!("{id}: {title}"),
+            Event::Shared::Archived(id) => format!("shared {id}"),
+            Event::Shared::Purged => "shared purged".to_string(),
+            Event::Ping => "ping".to_string(),
+            Event::Documents(_) | Event::Shared(_) => unreachable!(),
+        }
+    }
+}
+
+#[allow(non_upper_case_globals)]
+const wanted: u32 = 7;
+
+// `wanted` is a constant and `Total` a binding, whatever their case.
+#[allow(non_snake_case, non_upper_case_globals)]
+fn add_archived(key: u32, total: u32, event: Event::Event) -> Option<u32> {
+    nested! {
+        let (wanted, mut Total, Event::Documents::Lifecycle::Archived(id)) = (key, total, event)
+        else {
+            return None;
+        };
+        Total += *id;
+        Some(Total)
+    }
+}
+
+fn main() {
+    let expr = Expr::Binary::Add(
+        Expr::Expr::Lit(2),
+        Expr::Binary::Mul(Expr::Expr::Lit(3), Expr::Expr::Lit(4)),
+    );
+    assert_eq!(
+        expr,
+        Expr::Expr::Binary(Box::new(BinaryOp::BinaryOp::Add(
+            Expr::Expr::Lit(2),
+            Expr::Expr::Binary(Box::new(BinaryOp::BinaryOp::Mul(
+                Expr::Expr::Lit(3),
+                Expr::Expr::Lit(4),
+            ))),
+        )))
+    );
+    assert_eq!(eval(&expr), 14);
+    assert_eq!(
+        eval(&Expr::Binary::Mul(Expr::Expr::Lit(0), expr.clone())),
+        0
+    );
+
+    let archived = Event::Documents::Lifecycle::Archived(7);
+    assert_eq!(
+        archived,
+        Event::Event::Documents(Arc::new(DocumentsEvent::DocumentsEvent::Lifecycle(Rc::new(
+            LifecycleEvent::LifecycleEvent::Archived(7)
+        ))))
+    );
+    assert_eq!(describe(archived.clone()), "archived 7");
+    assert_eq!(describe(Event::Documents::Lifecycle::Archived(200)), "old 200");
+    assert_eq!(describe(Event::Documents::Lifecycle::Purged()), "purged");
+    assert_eq!(
+        describe(nested!(Event::Documents::Update {
+            id: 1,
+            title: "doc".to_string()
+        })),
+        "1: doc"
+    );
+    assert_eq!(describe(Event::Shared::Archived(3)), "shared 3");
+    assert_eq!(describe(Event::Shared::Purged()), "shared purged");
+    assert_eq!(describe(Event::Event::Ping), "ping");
+    assert_eq!(add_archived(7, 1, archived.clone()), Some(8));
+    assert_eq!(add_archived(8, 1, archived.clone()), None);
+    assert_eq!(add_archived(7, 1, Event::Documents::Lifecycle::Purged()), None);
+
+    nested! {
+        if let Event::Documents::Lifecycle::Archived(id) = &archived {
+            assert_eq!(*id, 7);
+        } else {
+            panic!("expected archived");
+        }
+
+        let mut stack = vec![Event::Shared::Archived(1), Event::Shared::Archived(2)];
+        let mut sum = 0;
+        while let Some(Event::Shared::Archived(id)) = stack.pop() {
+            sum += *id;
+        }
+        assert_eq!(sum, 3);
+
+        let Event::Documents::Lifecycle::Archived(id) = archived.clone() else {
+            panic!("expected archived");
+        };
+        assert_eq!(*id, 7);
+
+        // Bindings behind a pointer borrow from it.
+        let Expr::Binary::Add(lhs, _) = expr else {
+            panic!("expected addition");
+        };
+        assert_eq!(*lhs, Expr::Expr::Lit(2));
+
+        // The pointer is bound by value, so match a reference to keep using the event.
+        let is_lifecycle = matches!(&archived, Event::Documents::Lifecycle(_));
+        assert!(is_lifecycle);
+        let is_purged = matches!(&archived, Event::Documents::Lifecycle::Purged);
+        assert!(!is_purged);
+    }
+
+    assert!(nested_matches!(&archived, Event::Documents::Lifecycle::Archived(id) if *id == 7));
+    assert!(!nested_matches!(archived, Event::Shared));
+}
@@ -0,0 +1,90 @@
+use nestum::nestum;
+use std::collections::HashSet;
+
+#[nestum]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum]
+pub enum DocumentsEvent {
+    Update(String),
+    Lifecycle(Box<LifecycleEvent>),
+}
+
+#[nestum(selector)]
+pub enum Event {
+    Documents(u32, #[nestum(nested)] DocumentsEvent),
+    Ping,
+}
+
+#[nestum(companion, selector, rename_all = "snake_case", separator = ".")]
+pub enum Permission<T> {
+    Documents(DocumentsEvent),
+    Other(T),
+}
+
+fn main() {
+    let update = Event::Documents::Update(1, "doc".to_string());
+    let archived = Event::Documents::Lifecycle::Archived(1, 2);
+    let ping = Event::Event::Ping;
+
+    let documents: EventSelector = "Documents::*".parse().unwrap();
+    assert_eq!(documents, EventSelector::Subtree(EventKind::Documents));
+    assert!(documents.matches(&update));
+    assert!(documents.matches(&archived));
+    assert!(!documents.matches(&ping));
+
+    let lifecycle: EventSelector = "Documents::Lifecycle::*".parse().unwrap();
+    assert!(!lifecycle.matches(&update));
+    assert!(lifecycle.matches(&archived));
+
+    let leaf: EventSelector = "Documents::Update".parse().unwrap();
+    assert_eq!(leaf, EventSelector::Kind(EventKind::DocumentsUpdate));
+    assert!(leaf.matches(&update));
+    assert!(!leaf.matches(&archived));
+
+    let all: EventSelector = "*".parse().unwrap();
+    assert!(all.matches(&update) && all.matches(&ping));
+    assert!(
+        EventSelector::Subtree(EventKind::Documents)
+            .matches_kind(EventKind::DocumentsLifecyclePurged)
+    );
+
+    // Selectors display as they parse, so they can be stored as strings.
+    for selector in [all, documents, lifecycle, leaf] {
+        assert_eq!(selector.to_string().parse::<EventSelector>(), Ok(selector));
+    }
+
+    // Only leaves can be selected without a wildcard.
+    let error = "Documents".parse::<EventSelector>().unwrap_err();
+    assert_eq!(error.input(), "Documents");
+    assert_eq!(
+        error.expected(),
+        [
+            "*",
+            "Documents::*",
+            "Documents::Update",
+            "Documents::Lifecycle::*",
+            "Documents::Lifecycle::Archived",
+            "Documents::Lifecycle::Purged",
+            "Ping",
+        ]
+    );
+    assert!("Ping::*".parse::<EventSelector>().is_err());
+
+    // A user's grants, checked against each event.
+    let grants: HashSet<PermissionSelector> = ["documents.lifecycle.*", "other"]
+        .into_iter()
+        .map(|grant| grant.parse().unwrap())
+        .collect();
+    let allowed = |value: &Permission<u8>| grants.iter().any(|grant| grant.matches(value));
+    assert!(allowed(&permission::Documents::Lifecycle::Purged()));
+    assert!(allowed(&Permission::Other(1)));
+    assert!(!allowed(&permission::Documents::Update("doc".to_string())));
+    assert_eq!(
+        PermissionSelector::Subtree(PermissionKind::DocumentsLifecycle).to_string(),
+        "documents.lifecycle.*"
+    );
+}
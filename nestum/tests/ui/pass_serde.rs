@@ -0,0 +1,109 @@
+use nestum::nestum;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub title: String,
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentsEvent {
+    Created(Document),
+    Moved(u32, String),
+    Update { id: u32, r#type: String },
+    Lifecycle(Box<LifecycleEvent>),
+}
+
+#[nestum(serde, rename_all = "snake_case", separator = ".")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Documents(DocumentsEvent),
+    Ping,
+}
+
+#[nestum(serde, tag = "kind", content = "payload")]
+#[derive(Debug, PartialEq)]
+pub enum Tagged<T> {
+    Value(T),
+}
+
+fn round_trip(event: Event::Event, expected: serde_json::Value) {
+    assert_eq!(serde_json::to_value(&event).unwrap(), expected);
+    let text = serde_json::to_string(&event).unwrap();
+    assert_eq!(serde_json::from_str::<Event::Event>(&text).unwrap(), event);
+}
+
+fn main() {
+    round_trip(
+        Event::Documents::Created(Document {
+            title: "doc".to_string(),
+        }),
+        json!({ "type": "documents.created", "data": { "title": "doc" } }),
+    );
+    round_trip(
+        Event::Documents::Moved(1, "archive".to_string()),
+        json!({ "type": "documents.moved", "data": [1, "archive"] }),
+    );
+    round_trip(
+        Event::Documents::Update(2, "draft".to_string()),
+        json!({ "type": "documents.update", "data": { "id": 2, "type": "draft" } }),
+    );
+    round_trip(
+        Event::Documents::Lifecycle::Archived(3),
+        json!({ "type": "documents.lifecycle.archived", "data": 3 }),
+    );
+    round_trip(
+        Event::Documents::Lifecycle::Purged(),
+        json!({ "type": "documents.lifecycle.purged" }),
+    );
+    round_trip(Event::Event::Ping, json!({ "type": "ping" }));
+
+    // Unknown keys and a null payload on unit leaves are ignored.
+    let ping: Event::Event =
+        serde_json::from_str(r#"{"type":"ping","data":null,"trace":"x"}"#).unwrap();
+    assert_eq!(ping, Event::Event::Ping);
+
+    let error = serde_json::from_str::<Event::Event>(r#"{"type":"documents"}"#).unwrap_err();
+    assert!(error.to_string().contains("unknown variant `documents`"));
+    let error =
+        serde_json::from_str::<Event::Event>(r#"{"type":"documents.lifecycle.archived"}"#)
+            .unwrap_err();
+    assert!(error.to_string().contains("missing field `data`"));
+    let error = serde_json::from_str::<Event::Event>(r#"{"trace":"x"}"#).unwrap_err();
+    assert!(error.to_string().contains("missing field `type`"));
+
+    // The data is read as soon as it arrives, so the tag has to come first.
+    for text in [
+        r#"{"data":3,"type":"documents.lifecycle.archived"}"#,
+        r#"{"trace":"x","data":null,"type":"ping"}"#,
+    ] {
+        let error = serde_json::from_str::<Event::Event>(text).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("`data` must come after `type` when deserializing Event")
+        );
+    }
+    let error = serde_json::from_str::<Event::Event>(r#"{"type":"documents.update","data":{"id":1}}"#)
+        .unwrap_err();
+    assert!(error.to_string().contains("missing field `type`"));
+
+    let tagged = Tagged::Tagged::Value(vec![1u8, 2]);
+    let value = serde_json::to_value(&tagged).unwrap();
+    assert_eq!(value, json!({ "kind": "Value", "payload": [1, 2] }));
+    let text = serde_json::to_string(&tagged).unwrap();
+    assert_eq!(text, r#"{"kind":"Value","payload":[1,2]}"#);
+    assert_eq!(serde_json::from_str::<Tagged::Tagged<Vec<u8>>>(&text).unwrap(), tagged);
+    let reversed = r#"{"payload":[1,2],"kind":"Value"}"#;
+    let error = serde_json::from_str::<Tagged::Tagged<Vec<u8>>>(reversed).unwrap_err();
+    assert!(error.to_string().contains("`payload` must come after `kind`"));
+}
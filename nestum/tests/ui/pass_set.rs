@@ -0,0 +1,104 @@
+use nestum::nestum;
+
+#[nestum]
+#[derive(Debug, PartialEq)]
+pub enum DocumentPermission {
+    Read,
+    Write,
+    Delete,
+}
+
+#[nestum]
+#[derive(Debug, PartialEq)]
+pub enum BillingPermission {
+    View,
+    Refund,
+}
+
+#[nestum(set)]
+#[derive(Debug, PartialEq)]
+pub enum Permission {
+    Documents(DocumentPermission),
+    Billing(Box<BillingPermission>),
+    Admin,
+}
+
+// Grants can be built at compile time.
+const EDITOR: PermissionSet = PermissionSet::new()
+    .with(PermissionKind::Documents)
+    .without(PermissionKind::DocumentsDelete);
+const AUDITOR: PermissionSet =
+    PermissionSet::from_kinds([PermissionKind::DocumentsRead, PermissionKind::BillingView]);
+
+#[nestum(companion, set)]
+#[derive(Debug, PartialEq)]
+pub enum Wide {
+    V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, V10, V11, V12, V13, V14, V15, V16, V17, V18, V19,
+    V20, V21, V22, V23, V24, V25, V26, V27, V28, V29, V30, V31, V32, V33, V34, V35, V36, V37,
+    V38, V39, V40, V41, V42, V43, V44, V45, V46, V47, V48, V49, V50, V51, V52, V53, V54, V55,
+    V56, V57, V58, V59, V60, V61, V62, V63, V64, V65,
+}
+
+fn main() {
+    assert_eq!(EDITOR.len(), 2);
+    assert!(EDITOR.contains(&Permission::Documents::Write));
+    assert!(!EDITOR.contains(&Permission::Documents::Delete));
+    assert!(!EDITOR.contains_kind(PermissionKind::Documents));
+    assert!(EDITOR.contains_kind(PermissionKind::DocumentsRead));
+
+    let mut grants = PermissionSet::new();
+    assert!(grants.is_empty());
+    assert!(grants.insert(Permission::Permission::Admin));
+    assert!(!grants.insert(Permission::Permission::Admin));
+    grants.insert_kind(PermissionKind::Billing);
+    assert!(grants.contains(&Permission::Billing::Refund()));
+    assert!(grants.contains_kind(PermissionKind::Billing));
+    assert!(grants.remove(&Permission::Billing::Refund()));
+    assert!(!grants.remove(&Permission::Billing::Refund()));
+    grants.remove_kind(PermissionKind::Billing);
+    assert_eq!(grants, PermissionSet::from(PermissionKind::Admin));
+
+    // Set algebra.
+    assert_eq!((EDITOR | AUDITOR).len(), 3);
+    assert_eq!(EDITOR & AUDITOR, PermissionSet::from(PermissionKind::DocumentsRead));
+    assert_eq!(EDITOR - AUDITOR, PermissionSet::from(PermissionKind::DocumentsWrite));
+    assert_eq!((EDITOR ^ AUDITOR).len(), 2);
+    assert_eq!(!PermissionSet::EMPTY, PermissionSet::FULL);
+    assert_eq!((!EDITOR).len(), PermissionSet::FULL.len() - 2);
+    assert!(EDITOR.is_subset(&PermissionSet::FULL));
+    assert!(PermissionSet::FULL.is_superset(&AUDITOR));
+    assert!(EDITOR.is_disjoint(&PermissionSet::from(PermissionKind::Admin)));
+    let mut both = EDITOR;
+    both |= AUDITOR;
+    both -= PermissionSet::from(PermissionKind::Documents);
+    assert_eq!(both, PermissionSet::from(PermissionKind::BillingView));
+
+    // Iteration yields nested values, in declaration order.
+    let values: Vec<_> = (EDITOR | AUDITOR).into_iter().collect();
+    assert_eq!(
+        values,
+        [
+            Permission::Documents::Read,
+            Permission::Documents::Write,
+            Permission::Billing::View(),
+        ]
+    );
+    let collected: PermissionSet = values.into_iter().collect();
+    assert_eq!(collected, EDITOR | AUDITOR);
+    assert_eq!(
+        format!("{:?}", EDITOR),
+        "{DocumentsRead, DocumentsWrite}"
+    );
+
+    // Sets span as many words as the leaves need.
+    let mut wide = WideSet::new();
+    wide.insert(Wide::V0);
+    wide.insert(Wide::V63);
+    wide.insert(Wide::V64);
+    wide.insert(Wide::V65);
+    assert_eq!(wide.len(), 4);
+    assert!(wide.contains(&Wide::V64) && !wide.contains(&Wide::V1));
+    assert_eq!(wide.iter().last(), Some(Wide::V65));
+    assert_eq!(WideSet::FULL.len(), 66);
+    assert_eq!((!wide).len(), 62);
+}
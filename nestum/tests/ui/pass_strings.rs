@@ -0,0 +1,82 @@
+use nestum::nestum;
+use std::str::FromStr;
+
+#[nestum]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocumentPermission {
+    Read,
+    WriteAll,
+}
+
+#[nestum]
+#[derive(Debug, PartialEq)]
+pub enum AdminPermission {
+    ManageUsers,
+}
+
+#[nestum(display, from_str, rename_all = "snake_case", separator = ".")]
+#[derive(Debug, PartialEq)]
+pub enum Permission {
+    Documents(DocumentPermission),
+    Admin {
+        #[nestum(nested)]
+        scope: Box<AdminPermission>,
+    },
+    Everything,
+}
+
+#[nestum(companion, display, from_str, rename_all = "SCREAMING-KEBAB-CASE")]
+#[derive(Debug, PartialEq)]
+pub enum Role {
+    Owner,
+    Member(DocumentPermission),
+}
+
+#[nestum(display)]
+pub enum Plain {
+    Documents(DocumentPermission),
+}
+
+fn main() {
+    assert_eq!(Permission::Documents::Read.to_string(), "documents.read");
+    assert_eq!(Permission::Documents::WriteAll.to_string(), "documents.write_all");
+    assert_eq!(
+        Permission::Admin::ManageUsers().to_string(),
+        "admin.manage_users"
+    );
+    assert_eq!(Permission::Permission::Everything.to_string(), "everything");
+
+    assert_eq!(
+        "documents.write_all".parse(),
+        Ok(Permission::Documents::WriteAll)
+    );
+    assert_eq!(
+        Permission::Permission::from_str("admin.manage_users"),
+        Ok(Permission::Admin::ManageUsers())
+    );
+
+    // Round-trips through config and databases.
+    for text in ["documents.read", "documents.write_all", "admin.manage_users", "everything"] {
+        let permission: Permission::Permission = text.parse().unwrap();
+        assert_eq!(permission.to_string(), text);
+    }
+
+    let error = "documents".parse::<Permission::Permission>().unwrap_err();
+    assert_eq!(error.input(), "documents");
+    assert_eq!(
+        error.expected(),
+        ["documents.read", "documents.write_all", "admin.manage_users", "everything"]
+    );
+    assert_eq!(
+        error.to_string(),
+        "unknown Permission \"documents\"; expected one of: documents.read, \
+documents.write_all, admin.manage_users, everything"
+    );
+    let _: &dyn std::error::Error = &error;
+
+    assert_eq!(Role::Owner.to_string(), "OWNER");
+    assert_eq!("MEMBER::WRITE-ALL".parse(), Ok(role::Member::WriteAll));
+    assert!("member::write-all".parse::<Role>().is_err());
+
+    assert_eq!(Plain::Documents::Read.to_string(), "Documents::Read");
+}
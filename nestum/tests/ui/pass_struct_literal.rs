@@ -0,0 +1,58 @@
+use nestum::{nested, nestum};
+
+#[nestum]
+pub enum Inner {
+    Struct { x: i32, y: i32, label: String },
+    Tuple(u8),
+}
+
+#[nestum]
+pub enum Outer {
+    Wrap(Inner),
+    Plain { z: u8 },
+}
+
+#[nestum]
+pub enum Root {
+    Outer(Outer),
+}
+
+// `..base` is rejected, since the base may hold another variant; destructure it first.
+#[nestum]
+fn shifted(value: Outer::Outer, x: i32) -> Option<Outer::Outer> {
+    let Outer::Wrap::Struct { y, label, .. } = value else {
+        return None;
+    };
+    Some(Outer::Wrap::Struct { x, y, label })
+}
+
+fn main() {
+    let value: Outer::Outer = nested!(Outer::Wrap::Struct {
+        y: 2,
+        x: 1,
+        label: "a".to_string(),
+    });
+    let Outer::Outer::Wrap(Inner::Inner::Struct { x, y, label }) = &value else {
+        panic!("expected Outer::Wrap::Struct");
+    };
+    assert_eq!((*x, *y, label.as_str()), (1, 2, "a"));
+
+    let moved = shifted(value, 10).expect("value is Outer::Wrap::Struct");
+    let Outer::Outer::Wrap(Inner::Inner::Struct { x, y, .. }) = moved else {
+        panic!("expected Outer::Wrap::Struct");
+    };
+    assert_eq!((x, y), (10, 2));
+
+    let label = "deep".to_string();
+    let root = nested! { Root::Outer::Wrap::Struct { x: 3, y: 4, label } };
+    assert!(shifted(nested!(Outer::Plain { z: 1 }), 0).is_none());
+    assert!(nested!(matches!(
+        root,
+        Root::Outer::Wrap::Struct { x: 3, y: 4, ref label } if label == "deep"
+    )));
+
+    let plain = nested!(Outer::Plain { z: 7 });
+    assert!(matches!(plain, Outer::Outer::Plain { z: 7 }));
+    let tuple = nested!(Outer::Wrap::Tuple { 0: 9 });
+    assert!(nested!(matches!(tuple, Outer::Wrap::Tuple(9))));
+}
@@ -0,0 +1,65 @@
+use nestum::nestum;
+
+#[nestum(values)]
+#[derive(Debug, PartialEq)]
+pub enum LifecycleAction {
+    Archive,
+    Purge,
+}
+
+#[nestum]
+#[derive(Debug, PartialEq)]
+pub enum DocumentPermission {
+    Read,
+    Lifecycle(LifecycleAction),
+}
+
+#[nestum(paths, values)]
+#[derive(Debug, PartialEq)]
+pub enum Permission {
+    Documents(DocumentPermission),
+    Admin,
+}
+
+#[nestum(values)]
+#[derive(Debug, PartialEq)]
+pub enum Boxed {
+    Documents(Box<DocumentPermission>),
+    Admin,
+}
+
+#[nestum(companion, values)]
+#[derive(Debug, PartialEq)]
+pub enum Role {
+    Permission(Permission),
+    Guest,
+}
+
+// `ALL` is usable in const contexts.
+const PERMISSION_COUNT: usize = Permission::Permission::ALL.len();
+
+fn main() {
+    assert_eq!(PERMISSION_COUNT, 4);
+    assert_eq!(
+        Permission::Permission::ALL,
+        [
+            Permission::Documents::Read,
+            Permission::Documents::Lifecycle::Archive,
+            Permission::Documents::Lifecycle::Purge,
+            Permission::Permission::Admin,
+        ]
+    );
+    let paths: Vec<_> = Permission::Permission::all()
+        .map(|permission| permission.nested_path_str())
+        .collect();
+    assert_eq!(paths, Permission::Permission::PATHS);
+
+    // Values behind a `Box` are allocated, so only `all()` is generated.
+    let boxed: Vec<_> = Boxed::Boxed::all().collect();
+    assert_eq!(boxed.len(), 4);
+    assert_eq!(boxed[1], Boxed::Documents::Lifecycle::Archive());
+
+    assert_eq!(Role::ALL.len(), 5);
+    assert_eq!(Role::all().last(), Some(Role::Guest));
+    assert_eq!(LifecycleAction::LifecycleAction::ALL.len(), 2);
+}
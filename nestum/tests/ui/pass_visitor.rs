@@ -0,0 +1,156 @@
+use nestum::nestum;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TenantId(u32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub title: String,
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    Archived(u32),
+    Purged,
+}
+
+#[nestum]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentsEvent {
+    Update(Document),
+    Rename { id: u32, title: String },
+    Lifecycle(Box<LifecycleEvent>),
+}
+
+#[nestum(visitor)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Documents(TenantId, #[nestum(nested)] DocumentsEvent),
+    Shared(Rc<LifecycleEvent>),
+    Ping,
+}
+
+#[derive(Default)]
+struct Log(Vec<String>);
+
+impl EventVisitor for Log {
+    fn documents_update(&mut self, tenant: &TenantId, doc: &Document) {
+        self.0.push(format!("{} updated {}", tenant.0, doc.title));
+    }
+
+    fn documents_rename(&mut self, _: &TenantId, id: &u32, title: &String) {
+        self.0.push(format!("{id} renamed to {title}"));
+    }
+
+    fn shared_archived(&mut self, id: &u32) {
+        self.0.push(format!("shared {id}"));
+    }
+}
+
+struct Shout;
+
+impl EventVisitorMut for Shout {
+    fn documents_update(&mut self, _: &mut TenantId, doc: &mut Document) {
+        doc.title = doc.title.to_uppercase();
+    }
+
+    fn documents_lifecycle_archived(&mut self, _: &mut TenantId, id: &mut u32) {
+        *id += 1;
+    }
+
+    // Behind an `Rc`, the whole inner enum is the leaf.
+    fn shared(&mut self, event: &mut Rc<LifecycleEvent::LifecycleEvent>) {
+        *event = Rc::new(LifecycleEvent::LifecycleEvent::Purged);
+    }
+}
+
+struct Anonymize;
+
+impl EventFold for Anonymize {
+    fn documents_update(&mut self, _: TenantId, doc: Document) -> Event::Event {
+        Event::Documents::Update(TenantId(0), doc)
+    }
+
+    fn documents_lifecycle_purged(&mut self, tenant: TenantId) -> Event::Event {
+        let _ = tenant;
+        Event::Event::Ping
+    }
+}
+
+#[nestum(companion, visitor)]
+pub enum Wrapped<T> {
+    Value(T),
+    Lifecycle(LifecycleEvent),
+}
+
+struct Sum(u32);
+
+impl WrappedVisitor<u32> for Sum {
+    fn value(&mut self, value: &u32) {
+        self.0 += value;
+    }
+
+    fn lifecycle_archived(&mut self, id: &u32) {
+        self.0 += id;
+    }
+}
+
+fn main() {
+    let tenant = TenantId(7);
+    let doc = Document {
+        title: "doc".to_string(),
+    };
+    let events = vec![
+        Event::Documents::Update(tenant, doc.clone()),
+        Event::Documents::Rename(tenant, 1, "new".to_string()),
+        Event::Documents::Lifecycle::Archived(tenant, 2),
+        Event::Shared::Archived(3),
+        Event::Event::Ping,
+    ];
+
+    let mut log = Log::default();
+    for event in &events {
+        event.accept(&mut log);
+    }
+    assert_eq!(log.0, ["7 updated doc", "1 renamed to new", "shared 3"]);
+
+    let mut events = events;
+    for event in &mut events {
+        event.accept_mut(&mut Shout);
+    }
+    assert_eq!(
+        events[0],
+        Event::Documents::Update(
+            tenant,
+            Document {
+                title: "DOC".to_string()
+            }
+        )
+    );
+    assert_eq!(events[2], Event::Documents::Lifecycle::Archived(tenant, 3));
+    assert_eq!(events[3], Event::Shared::Purged());
+
+    let folded: Vec<_> = events.into_iter().map(|event| event.fold(&mut Anonymize)).collect();
+    assert_eq!(
+        folded[0],
+        Event::Documents::Update(
+            TenantId(0),
+            Document {
+                title: "DOC".to_string()
+            }
+        )
+    );
+    // Unhandled leaves are rebuilt unchanged.
+    assert_eq!(folded[1], Event::Documents::Rename(tenant, 1, "new".to_string()));
+    assert_eq!(
+        Event::Documents::Lifecycle::Purged(tenant).fold(&mut Anonymize),
+        Event::Event::Ping
+    );
+
+    let mut sum = Sum(0);
+    Wrapped::Value(1).accept(&mut sum);
+    Wrapped::<u32>::Lifecycle(LifecycleEvent::LifecycleEvent::Archived(2)).accept(&mut sum);
+    assert_eq!(sum.0, 3);
+}
@@ -0,0 +1,11 @@
+use nestum::nestum;
+
+// Loaded through #[path], so child modules sit next to this file.
+#[path = "lifecycle_events.rs"]
+pub mod lifecycle;
+
+#[nestum]
+pub enum DocumentsEvent {
+    Created,
+    Deleted,
+}
@@ -0,0 +1,6 @@
+use nestum::nestum;
+
+#[nestum]
+pub enum Inner {
+    A,
+}
@@ -0,0 +1,7 @@
+use nestum::nestum;
+
+#[nestum]
+pub enum LifecycleEvent {
+    Archived,
+    Purged,
+}